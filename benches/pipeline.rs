@@ -0,0 +1,208 @@
+use std::hint::black_box;
+
+use bigdecimal::BigDecimal;
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use silhouette::{
+    file_reader::csv_stream,
+    ledger::engine::PaymentsEngine,
+    model::{CSVRecord, TxType, deserialize_decimal},
+    output::{OutputFormat, write_accounts},
+    precision::PrecisionPolicy,
+    workload::{Workload, WorkloadConfig},
+};
+
+const SIZES: [u32; 3] = [1_000, 10_000, 100_000];
+const CLIENTS: u16 = 1_000;
+
+fn workload_csv(transactions: u32) -> Vec<u8> {
+    let config = WorkloadConfig {
+        clients: CLIENTS,
+        transactions,
+        ..WorkloadConfig::default()
+    };
+    let mut buffer = Vec::new();
+    Workload::generate(&config).write_csv(&mut buffer).unwrap();
+    buffer
+}
+
+fn record(r#type: TxType, tx: u32, amount: Option<i64>) -> CSVRecord {
+    CSVRecord {
+        r#type,
+        client: ((tx % u32::from(CLIENTS)) as u16 + 1).into(),
+        tx: tx.into(),
+        amount: amount.map(|amount| BigDecimal::new(amount.into(), 4)),
+    }
+}
+
+fn amount_for(r#type: &TxType) -> Option<i64> {
+    match r#type {
+        TxType::Deposit => Some(1_000_000),
+        TxType::Withdrawal => Some(1),
+        _ => None,
+    }
+}
+
+fn records(r#type: &TxType, n: u32) -> Vec<CSVRecord> {
+    (1..=n)
+        .map(|tx| record(r#type.clone(), tx, amount_for(r#type)))
+        .collect()
+}
+
+/// An engine that has already processed `n` records of each type in `steps`.
+fn engine_with(steps: &[TxType], n: u32) -> PaymentsEngine {
+    let mut payment_engine = PaymentsEngine::default();
+    for r#type in steps {
+        for record in records(r#type, n) {
+            payment_engine.process_csv_record(record).unwrap();
+        }
+    }
+    payment_engine
+}
+
+fn bench_csv_stream(c: &mut Criterion) {
+    let mut group = c.benchmark_group("csv_stream");
+    for size in SIZES {
+        let input = workload_csv(size);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+            b.iter(|| {
+                for record in csv_stream(input.as_slice()) {
+                    black_box(record.unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_deserialize_decimal(c: &mut Criterion) {
+    #[derive(serde::Deserialize)]
+    struct Amount {
+        #[serde(default, deserialize_with = "deserialize_decimal")]
+        amount: Option<BigDecimal>,
+    }
+
+    let mut group = c.benchmark_group("deserialize_decimal");
+    for size in SIZES {
+        let mut input = String::from("amount\n");
+        for i in 0..size {
+            input.push_str(&format!("{}.{:05}\n", i * 7, i));
+        }
+        group.throughput(Throughput::Elements(u64::from(size)));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+            b.iter(|| {
+                let mut reader = csv::Reader::from_reader(input.as_bytes());
+                for amount in reader.deserialize::<Amount>() {
+                    black_box(amount.unwrap().amount);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_process(c: &mut Criterion) {
+    use TxType::{Chargeback, Deposit, Dispute, Resolve, Withdrawal};
+
+    let paths: [(TxType, &[TxType]); 5] = [
+        (Deposit, &[]),
+        (Withdrawal, &[]),
+        (Dispute, &[Deposit]),
+        (Resolve, &[Deposit, Dispute]),
+        (Chargeback, &[Deposit, Dispute]),
+    ];
+
+    for (r#type, setup) in paths {
+        let mut group = c.benchmark_group(format!("process_{type:?}").to_lowercase());
+        for size in SIZES {
+            group.throughput(Throughput::Elements(u64::from(size)));
+            group.bench_function(BenchmarkId::from_parameter(size), |b| {
+                b.iter_batched(
+                    || {
+                        let mut payment_engine = engine_with(setup, size);
+                        // Fund withdrawals from deposits stored under a separate range of TxIds
+                        if r#type == Withdrawal {
+                            for record in records(&Deposit, size) {
+                                let tx = (record.tx.0 + size).into();
+                                payment_engine
+                                    .process_csv_record(CSVRecord { tx, ..record })
+                                    .unwrap();
+                            }
+                        }
+                        (payment_engine, records(&r#type, size))
+                    },
+                    |(mut payment_engine, records)| {
+                        for record in records {
+                            black_box(payment_engine.process_csv_record(record)).unwrap();
+                        }
+                        payment_engine
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+    }
+}
+
+fn bench_end_to_end(c: &mut Criterion) {
+    let mut group = c.benchmark_group("end_to_end");
+    for size in SIZES {
+        let input = workload_csv(size);
+        group.throughput(Throughput::Elements(u64::from(size)));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &input, |b, input| {
+            b.iter(|| {
+                let mut payment_engine = PaymentsEngine::default();
+                for record in csv_stream(input.as_slice()) {
+                    let _ = payment_engine.process_csv_record(record.unwrap());
+                }
+                payment_engine
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_write_accounts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write_accounts");
+    for size in SIZES {
+        let config = WorkloadConfig {
+            clients: u16::MAX,
+            transactions: size,
+            ..WorkloadConfig::default()
+        };
+        let mut payment_engine = PaymentsEngine::default();
+        for record in Workload::generate(&config).records {
+            let _ = payment_engine.process_csv_record(record);
+        }
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(size),
+            &payment_engine,
+            |b, payment_engine| {
+                b.iter(|| {
+                    let mut buffer = Vec::new();
+                    write_accounts(
+                        &payment_engine.client_manager,
+                        OutputFormat::Csv,
+                        &PrecisionPolicy::default(),
+                        &mut buffer,
+                    )
+                    .unwrap();
+                    buffer
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_csv_stream,
+    bench_deserialize_decimal,
+    bench_process,
+    bench_end_to_end,
+    bench_write_accounts
+);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use silhouette::file_reader::csv_stream;
+
+fuzz_target!(|data: &[u8]| {
+    for record in csv_stream(data) {
+        let _ = record;
+    }
+});
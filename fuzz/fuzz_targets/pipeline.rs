@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use silhouette::{
+    file_reader::csv_stream,
+    ledger::{engine::PaymentsEngine, invariants::InvariantChecker},
+    output::output_records,
+};
+
+fuzz_target!(|data: &[u8]| {
+    let mut payment_engine = PaymentsEngine::default();
+    let mut checker = InvariantChecker::default();
+
+    for record in csv_stream(data) {
+        let Ok(record) = record else {
+            continue;
+        };
+        let _ = payment_engine.process_csv_record(record);
+        if let Err(violation) = checker.check(&payment_engine) {
+            panic!("invariant violated: {violation}");
+        }
+    }
+
+    let mut csv_wtr = csv::Writer::from_writer(Vec::new());
+    for account in output_records(&payment_engine.client_manager, payment_engine.precision()) {
+        csv_wtr.serialize(account).expect("OutputRecord should always serialise");
+    }
+    csv_wtr.flush().expect("writing to a Vec cannot fail");
+});
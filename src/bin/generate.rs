@@ -0,0 +1,70 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::PathBuf,
+};
+
+use clap::{CommandFactory as _, Parser, error::ErrorKind};
+use silhouette::workload::{Workload, WorkloadConfig};
+
+/// Writes a synthetic transaction CSV suitable as input to `cli`.
+#[derive(Parser)]
+struct Args {
+    #[arg(long, default_value_t = 100)]
+    clients: u16,
+    #[arg(long, default_value_t = 10_000)]
+    transactions: u32,
+    /// Share of deposit/withdrawal records that are withdrawals
+    #[arg(long, default_value_t = 0.3)]
+    withdrawal_ratio: f64,
+    #[arg(long, default_value_t = 0.05)]
+    dispute_ratio: f64,
+    #[arg(long, default_value_t = 0.03)]
+    resolve_ratio: f64,
+    #[arg(long, default_value_t = 0.01)]
+    chargeback_ratio: f64,
+    /// Share of records crafted to be rejected by the engine
+    #[arg(long, default_value_t = 0.01)]
+    error_rate: f64,
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Write to this path instead of stdout
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Also write the expected final account states to this path
+    #[arg(long)]
+    expected: Option<PathBuf>,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+    let config = WorkloadConfig {
+        clients: args.clients,
+        transactions: args.transactions,
+        withdrawal_ratio: args.withdrawal_ratio,
+        dispute_ratio: args.dispute_ratio,
+        resolve_ratio: args.resolve_ratio,
+        chargeback_ratio: args.chargeback_ratio,
+        error_rate: args.error_rate,
+        seed: args.seed,
+    };
+
+    if let Err(err) = config.validate() {
+        Args::command()
+            .error(ErrorKind::ValueValidation, err)
+            .exit();
+    }
+
+    let workload = Workload::generate(&config);
+
+    match args.output {
+        Some(path) => workload.write_csv(BufWriter::new(File::create(path)?))?,
+        None => workload.write_csv(io::stdout().lock())?,
+    }
+
+    if let Some(path) = args.expected {
+        workload.write_expected(BufWriter::new(File::create(path)?))?;
+    }
+
+    Ok(())
+}
@@ -1,33 +1,925 @@
-use std::io::{BufReader, Write};
-use std::{fs::File, io};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::mpsc::{self, Receiver},
+};
 
+use bigdecimal::RoundingMode;
+use clap::{Args, Parser, Subcommand, ValueEnum, value_parser};
+use serde::Serialize;
 use silhouette::{
-    file_reader::csv_stream, ledger::engine::PaymentsEngine, output::write_accounts_to_stdout,
+    checkpoint::{Checkpoint, CheckpointWriter, InputFingerprint, InputPosition},
+    events::EngineEvent,
+    file_reader::{
+        InputFormat, ReadError, decompress, is_compressed, open_path, open_path_at,
+        positioned_record_stream,
+    },
+    ledger::engine::PaymentsEngine,
+    model::{CSVRecord, RawRecord},
+    output::{
+        ChangeRecord, OutputError, OutputFormat, OutputRecord, RecordWriter, balance_records,
+        output_records, read_accounts,
+    },
+    precision::{MAX_SCALE, PrecisionPolicy, parse_rounding_mode},
+    schema::CsvSchema,
+    statement::Statement,
+    transaction::TransactionError,
+    validation::{Validator, check_strict},
 };
 
-fn main() -> Result<(), csv::Error> {
-    let file_path = std::env::args().nth(1).expect("No file_path was provided");
-    let Ok(file) = File::open(&file_path) else {
-        panic!("Failed to open file at path: {file_path}");
+#[path = "main/follow.rs"]
+mod follow;
+#[path = "main/watch.rs"]
+mod watch;
+
+/// Exit code when the command ran but found a problem: validation failures, differing
+/// accounts, or an `inspect` target that does not exist.
+const EXIT_FINDINGS: u8 = 1;
+
+#[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// With no subcommand the arguments are treated as `process`
+    #[command(flatten)]
+    process: ProcessArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply the transactions and write the final accounts to stdout
+    Process(ProcessArgs),
+    /// Parse and check the transactions without applying them
+    Validate(InputArgs),
+    /// Look up a single client or transaction after processing
+    Inspect {
+        #[command(flatten)]
+        args: ProcessArgs,
+        #[arg(long, conflicts_with = "tx", required_unless_present = "tx")]
+        client: Option<u16>,
+        #[arg(long)]
+        tx: Option<u32>,
+    },
+    /// Compare two account CSVs
+    Diff { left: PathBuf, right: PathBuf },
+    /// List one client's postings over a range of input records, between its opening and
+    /// closing balances
+    Statement {
+        #[command(flatten)]
+        args: ProcessArgs,
+        #[arg(long)]
+        client: u16,
+        /// First input record covered, counted across all inputs
+        #[arg(long, default_value_t = 1)]
+        from: u64,
+        /// Last input record covered. Defaults to the last record
+        #[arg(long)]
+        to: Option<u64>,
+        /// Write a plain-text table instead of `--format`
+        #[arg(long)]
+        text: bool,
+    },
+    /// Apply lines as they are appended to a CSV file, like `tail -f`. SIGUSR1 dumps the
+    /// accounts; SIGINT or SIGTERM dumps them and exits
+    Follow(follow::FollowArgs),
+    /// Process files dropped into an inbox directory, writing per-file results to an outbox.
+    /// Each file is applied at most once, recognised by its SHA-256
+    Watch(watch::WatchArgs),
+}
+
+#[derive(Args)]
+struct ProcessArgs {
+    #[command(flatten)]
+    input: InputArgs,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(flatten)]
+    checkpoint: CheckpointArgs,
+    /// Also write a CSV row per applied record here: how it changed the client's balances, and
+    /// the balances after
+    #[arg(long, conflicts_with = "resume")]
+    changes: Option<PathBuf>,
+    /// Report the accounts as they stood after this record, counted across all inputs, rather
+    /// than at the end
+    #[arg(long)]
+    as_of: Option<u64>,
+    /// What a rejected record in a `batch` does
+    #[arg(long, value_enum, default_value_t = BatchPolicy::Rollback)]
+    batch_policy: BatchPolicy,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BatchPolicy {
+    /// Roll back every change from the batch
+    Rollback,
+    /// Skip just the rejected record and apply the rest, as if there were no batch
+    Skip,
+}
+
+impl ProcessArgs {
+    /// The accounts at the end, or after record `--as-of` from the balance history.
+    fn accounts(&self, engine: &PaymentsEngine) -> Result<Vec<OutputRecord>, CliError> {
+        let display = self.output.display();
+        let Some(as_of) = self.as_of else {
+            return Ok(output_records(&engine.client_manager, &display).collect());
+        };
+        let history = engine.history().ok_or(CliError::NoHistory)?;
+        if as_of < history.start() {
+            return Err(CliError::AsOf {
+                as_of,
+                start: history.start(),
+            });
+        }
+        let accounts = history.accounts_at(as_of, &engine.journal);
+        Ok(balance_records(
+            accounts
+                .iter()
+                .map(|(client, balances)| (*client, balances)),
+            &display,
+        )
+        .collect())
+    }
+}
+
+#[derive(Args)]
+struct CheckpointArgs {
+    /// Save the engine state and input position here every `--checkpoint-every` records
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+    /// Records applied between checkpoints
+    #[arg(long, default_value_t = 1_000_000, requires = "checkpoint")]
+    checkpoint_every: usize,
+    /// Continue from `--checkpoint` if it exists. The same inputs must be given again, unchanged
+    /// and in the same order
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+}
+
+impl CheckpointArgs {
+    fn load(&self, precision: PrecisionPolicy) -> Result<Option<Checkpoint>, CliError> {
+        match &self.checkpoint {
+            Some(path) if self.resume => {
+                Checkpoint::load(path, precision).map_err(|source| CliError::Open {
+                    input: Input::File(path.clone()),
+                    source,
+                })
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Saves a checkpoint through `writer` once `checkpoint_every` records have been read since
+    /// the last one at `saved`.
+    fn save_every(
+        &self,
+        writer: &mut Option<CheckpointWriter>,
+        records: usize,
+        saved: &mut usize,
+        position: impl FnOnce() -> InputPosition,
+        engine: &mut PaymentsEngine,
+    ) -> Result<(), CliError> {
+        match &self.checkpoint {
+            Some(path) if records - *saved >= self.checkpoint_every => {
+                *saved = records;
+                writer
+                    .get_or_insert_with(|| CheckpointWriter::new(path))
+                    .save(0, records, Some(&position()), engine)
+                    .map_err(|err| CliError::Output(err.into()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Args)]
+struct EngineArgs {
+    /// Decimal places amounts are held to, at most 28
+    #[arg(long, default_value_t = 4, value_parser = value_parser!(u32).range(0..=MAX_SCALE))]
+    scale: u32,
+    /// Rounding mode for amounts with more decimal places than `--scale`
+    #[arg(long, default_value = "half-even", value_parser = parse_rounding_mode)]
+    rounding: RoundingMode,
+    /// Reject amounts with more decimal places than `--scale` instead of rounding them
+    #[arg(long)]
+    reject_excess_precision: bool,
+}
+
+impl EngineArgs {
+    fn precision(&self) -> PrecisionPolicy {
+        PrecisionPolicy {
+            scale: self.scale.into(),
+            rounding: self.rounding,
+            reject_excess: self.reject_excess_precision,
+        }
+    }
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Output format: csv, json or jsonl
+    #[arg(long, default_value = "csv")]
+    format: OutputFormat,
+    /// Decimal places written in the output, rounded half-even, at most 28
+    #[arg(long, default_value_t = 4, value_parser = value_parser!(u32).range(0..=MAX_SCALE))]
+    display_scale: u32,
+}
+
+impl OutputArgs {
+    fn display(&self) -> PrecisionPolicy {
+        PrecisionPolicy::with_scale(self.display_scale.into())
+    }
+}
+
+#[derive(Args)]
+struct InputArgs {
+    /// Transaction CSVs or glob patterns, processed in order. `-` or nothing reads stdin
+    inputs: Vec<String>,
+    /// Input format: csv or jsonl. Detected from the extension or content when omitted
+    #[arg(long)]
+    input_format: Option<InputFormat>,
+    /// JSON file mapping CSV columns, header presence and delimiter
+    #[arg(long)]
+    schema: Option<PathBuf>,
+    /// Format of the rejection lines written to stderr
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ErrorFormat {
+    /// `<input>: record <n>: Error processing Transaction due to <code>: <message>`
+    Text,
+    /// One `{"input", "record", "error": {"code", ...}}` object per line
+    Json,
+}
+
+#[derive(Serialize)]
+struct Rejection<'a, E = TransactionError> {
+    input: String,
+    record: usize,
+    /// Set when the record failed an atomic batch, all of which was rolled back
+    #[serde(skip_serializing_if = "Option::is_none")]
+    batch: Option<&'a str>,
+    error: &'a E,
+}
+
+/// The `error` of a rejection for a row that could not be parsed.
+#[derive(Serialize)]
+struct Malformed {
+    code: &'static str,
+    message: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum CliError {
+    #[error("failed to open {input}: {source}")]
+    Open { input: Input, source: io::Error },
+    #[error("invalid glob pattern: {0}")]
+    Pattern(#[from] glob::PatternError),
+    #[error("no inputs match {0}")]
+    NoMatches(String),
+    #[error("invalid schema {}: {source}", path.display())]
+    Schema {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("{input}: malformed input: {source}")]
+    Parse { input: Input, source: ReadError },
+    #[error("failed to write output: {0}")]
+    Output(#[from] OutputError),
+    #[error("--as-of {as_of} is before record {start}, where the resumed balance history starts")]
+    AsOf { as_of: u64, start: u64 },
+    #[error("--as-of: the engine kept no balance history")]
+    NoHistory,
+    #[error(
+        "--resume: the inputs have changed since the checkpoint; give the same files, unmodified and in the same order"
+    )]
+    ResumeMismatch,
+    #[error("{0} was replaced or truncated since the checkpoint was taken")]
+    FollowMismatch(Input),
+}
+
+impl CliError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            CliError::Open { .. } | CliError::Pattern(_) | CliError::NoMatches(_) => {
+                ExitCode::from(3)
+            }
+            CliError::Schema { .. }
+            | CliError::AsOf { .. }
+            | CliError::NoHistory
+            | CliError::ResumeMismatch
+            | CliError::FollowMismatch(_) => ExitCode::from(2),
+            CliError::Parse { .. } => ExitCode::from(4),
+            CliError::Output(_) => ExitCode::from(5),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+impl Input {
+    fn fingerprint(&self) -> Result<InputFingerprint, CliError> {
+        match self {
+            Input::Stdin => Ok(InputFingerprint::stdin()),
+            Input::File(path) => InputFingerprint::of(path).map_err(|source| CliError::Open {
+                input: self.clone(),
+                source,
+            }),
+        }
+    }
+}
+
+/// Where a row was read: its input and that input's index, the row's 1-based record number
+/// within it, and the byte offset just past it when the input is an uncompressed file.
+struct Row<'a> {
+    input: &'a Input,
+    index: usize,
+    n: usize,
+    offset: Option<u64>,
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Stdin => write!(f, "<stdin>"),
+            Input::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+fn open(input: &Input) -> Result<Box<dyn io::BufRead>, CliError> {
+    match input {
+        Input::Stdin => decompress(io::stdin().lock()),
+        Input::File(path) => open_path(path),
+    }
+    .map_err(|source| CliError::Open {
+        input: input.clone(),
+        source,
+    })
+}
+
+/// Reports a row that could not be parsed, in the shape of a rejection with the code
+/// `malformed`.
+fn report_malformed(
+    wtr: &mut impl Write,
+    format: ErrorFormat,
+    input: &Input,
+    n: usize,
+    err: &ReadError,
+) {
+    let _ = match format {
+        ErrorFormat::Text => writeln!(wtr, "{input}: record {n}: malformed input: {err}"),
+        ErrorFormat::Json => {
+            let rejection = Rejection {
+                input: input.to_string(),
+                record: n,
+                batch: None,
+                error: &Malformed {
+                    code: "malformed",
+                    message: err.to_string(),
+                },
+            };
+            serde_json::to_writer(&mut *wtr, &rejection)
+                .map_err(io::Error::from)
+                .and_then(|()| writeln!(wtr))
+        }
+    };
+}
+
+fn report(
+    wtr: &mut impl Write,
+    format: ErrorFormat,
+    input: &Input,
+    n: usize,
+    batch: Option<&str>,
+    err: &TransactionError,
+) {
+    let _ = match format {
+        ErrorFormat::Text => {
+            let rolled_back = batch
+                .map(|batch| format!("; batch {batch} rolled back"))
+                .unwrap_or_default();
+            writeln!(
+                wtr,
+                "{input}: record {n}: Error processing Transaction due to {}: {err}{rolled_back}",
+                err.code()
+            )
+        }
+        ErrorFormat::Json => {
+            let rejection = Rejection {
+                input: input.to_string(),
+                record: n,
+                batch,
+                error: err,
+            };
+            serde_json::to_writer(&mut *wtr, &rejection)
+                .map_err(io::Error::from)
+                .and_then(|()| writeln!(wtr))
+        }
     };
-    let buffer = BufReader::new(file);
+}
+
+impl InputArgs {
+    /// Expands glob patterns (sorted per pattern) while keeping the argument order.
+    fn resolve(&self) -> Result<Vec<Input>, CliError> {
+        if self.inputs.is_empty() {
+            return Ok(vec![Input::Stdin]);
+        }
+
+        let mut inputs = Vec::new();
+        for arg in &self.inputs {
+            if arg == "-" {
+                inputs.push(Input::Stdin);
+            } else if arg.contains(['*', '?', '[']) {
+                let mut matches = glob::glob(arg)?
+                    .filter_map(Result::ok)
+                    .filter(|path| path.is_file())
+                    .collect::<Vec<_>>();
+                if matches.is_empty() {
+                    return Err(CliError::NoMatches(arg.clone()));
+                }
+                matches.sort();
+                inputs.extend(matches.into_iter().map(Input::File));
+            } else {
+                inputs.push(Input::File(PathBuf::from(arg)));
+            }
+        }
+        Ok(inputs)
+    }
+
+    fn schema(&self) -> Result<Option<CsvSchema>, CliError> {
+        let Some(path) = &self.schema else {
+            return Ok(None);
+        };
+        let reader = open(&Input::File(path.clone()))?;
+        serde_json::from_reader(reader)
+            .map(Some)
+            .map_err(|source| CliError::Schema {
+                path: path.clone(),
+                source,
+            })
+    }
+
+    fn report(&self, wtr: &mut impl Write, input: &Input, n: usize, err: &TransactionError) {
+        report(wtr, self.error_format, input, n, None, err);
+    }
+
+    fn report_malformed(&self, wtr: &mut impl Write, input: &Input, n: usize, err: &ReadError) {
+        report_malformed(wtr, self.error_format, input, n, err);
+    }
+
+    /// Streams every row from `inputs` in order, starting from `start` when resuming. Rows that
+    /// cannot be parsed are passed on as errors; an input stops at the first that cannot be read.
+    fn for_each_row(
+        &self,
+        inputs: &[Input],
+        start: Option<&InputPosition>,
+        mut f: impl FnMut(&Row, Result<RawRecord, ReadError>) -> Result<(), CliError>,
+    ) -> Result<(), CliError> {
+        let schema = self.schema()?;
+        for (index, input) in inputs.iter().enumerate() {
+            let (records, offset) = match start {
+                Some(start) if index < start.input => continue,
+                Some(start) if index == start.input => (start.records, start.offset),
+                _ => (0, None),
+            };
+            let open_err = |source| CliError::Open {
+                input: input.clone(),
+                source,
+            };
+            let parse_err = |source| CliError::Parse {
+                input: input.clone(),
+                source,
+            };
+            let format = self.input_format.or_else(|| match input {
+                Input::File(path) => InputFormat::from_extension(path),
+                Input::Stdin => None,
+            });
+            let seekable = match input {
+                Input::File(path) => !is_compressed(path).map_err(open_err)?,
+                Input::Stdin => false,
+            };
+
+            // An uncompressed input resumes at its offset; anything else is read again up to it
+            let (reader, base, first, skip) = match (input, offset) {
+                (Input::File(path), Some(offset)) if seekable => {
+                    let (reader, base) =
+                        open_path_at(path, offset, format, schema.as_ref()).map_err(open_err)?;
+                    (reader, base, records, 0)
+                }
+                _ => (open(input)?, 0, 0, records),
+            };
+            let rows = positioned_record_stream(reader, format, schema.as_ref())
+                .map_err(parse_err)?
+                .enumerate()
+                .skip(skip);
+
+            for (idx, (end, row)) in rows {
+                let unreadable = match &row {
+                    Err(ReadError::Io(_)) => true,
+                    Err(ReadError::Csv(err)) => err.is_io_error(),
+                    _ => false,
+                };
+                let at = Row {
+                    input,
+                    index,
+                    n: first + idx + 1,
+                    offset: seekable.then_some(base + end),
+                };
+                f(&at, row)?;
+                if unreadable {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [`for_each_row`](Self::for_each_row), but with each row's batch id, and records
+    /// breaking the per-type rules of [`check_strict`] passed on as errors. The first malformed
+    /// row is fatal.
+    fn for_each_record(
+        &self,
+        inputs: &[Input],
+        start: Option<&InputPosition>,
+        mut f: impl FnMut(
+            &Row,
+            Option<&str>,
+            Result<CSVRecord, TransactionError>,
+        ) -> Result<(), CliError>,
+    ) -> Result<(), CliError> {
+        self.for_each_row(inputs, start, |at, row| {
+            let mut record = row.map_err(|source| CliError::Parse {
+                input: at.input.clone(),
+                source,
+            })?;
+            let batch = record.batch.take();
+            f(at, batch.as_deref(), check_strict(record))
+        })
+    }
+}
+
+/// The records of an atomic batch, held back until the batch ends.
+struct PendingBatch {
+    input: Input,
+    id: String,
+    /// Engine position before the batch's first record
+    position: u64,
+    records: Vec<(usize, CSVRecord)>,
+    /// The first record failing strict validation, which fails the whole batch
+    invalid: Option<(usize, TransactionError)>,
+}
+
+impl PendingBatch {
+    fn new(input: &Input, id: &str, position: u64) -> Self {
+        Self {
+            input: input.clone(),
+            id: id.to_owned(),
+            position,
+            records: Vec::new(),
+            invalid: None,
+        }
+    }
 
-    let mut payment_engine = PaymentsEngine::default();
+    fn push(&mut self, n: usize, record: Result<CSVRecord, TransactionError>) {
+        match record {
+            Ok(record) => self.records.push((n, record)),
+            Err(err) => {
+                self.invalid.get_or_insert((n, err));
+            }
+        }
+    }
+
+    /// Applies the batch, reporting the record that failed it if it was rolled back.
+    fn apply(self, engine: &mut PaymentsEngine, format: ErrorFormat, wtr: &mut impl Write) {
+        let failed = match self.invalid {
+            Some(invalid) => Some(invalid),
+            None => {
+                let numbers = self.records.iter().map(|(n, _)| *n).collect::<Vec<_>>();
+                engine.set_position(self.position);
+                engine
+                    .process_batch(self.records.into_iter().map(|(_, record)| record))
+                    .err()
+                    .map(|err| (numbers[err.index], err.error))
+            }
+        };
+        if let Some((n, err)) = failed {
+            report(wtr, format, &self.input, n, Some(&self.id), &err);
+        }
+    }
+}
+
+/// Writes the `--changes` file from the engine's events as records are applied.
+struct ChangeLog {
+    events: Receiver<EngineEvent>,
+    wtr: RecordWriter<BufWriter<File>>,
+    display: PrecisionPolicy,
+}
+
+impl ChangeLog {
+    fn create(
+        path: &Path,
+        engine: &mut PaymentsEngine,
+        display: PrecisionPolicy,
+    ) -> Result<Self, CliError> {
+        let file = File::create(path).map_err(|source| CliError::Open {
+            input: Input::File(path.to_path_buf()),
+            source,
+        })?;
+        let (sender, events) = mpsc::channel();
+        engine.subscribe(sender);
+        Ok(Self {
+            events,
+            wtr: RecordWriter::new(OutputFormat::Csv, BufWriter::new(file)),
+            display,
+        })
+    }
+
+    /// Writes the rows for every event emitted since the last call.
+    fn drain(&mut self) -> Result<(), CliError> {
+        for event in self.events.try_iter() {
+            if let Some(row) = ChangeRecord::from_event(&event, &self.display) {
+                self.wtr.write(&row)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn run_engine(args: &ProcessArgs) -> Result<PaymentsEngine, CliError> {
+    let precision = args.engine.precision();
+    let inputs = args.input.resolve()?;
+    let fingerprints = inputs
+        .iter()
+        .map(Input::fingerprint)
+        .collect::<Result<Vec<_>, _>>()?;
+    let checkpoint = args.checkpoint.load(precision)?;
+    let (mut payment_engine, start, resume_at) = match checkpoint {
+        Some(checkpoint) => {
+            let start = checkpoint
+                .input_position
+                .filter(|start| start.inputs == fingerprints)
+                .ok_or(CliError::ResumeMismatch)?;
+            (checkpoint.engine, Some(start), checkpoint.records)
+        }
+        None => (PaymentsEngine::with_precision(precision), None, 0),
+    };
+    if args.as_of.is_some() {
+        payment_engine.keep_history();
+    }
+    let format = args.input.error_format;
     let mut stderr = io::stderr().lock();
+    let mut records = resume_at;
+    let mut saved = resume_at;
+    let mut writer = None;
+    let mut pending: Option<PendingBatch> = None;
+    let mut changes = match &args.changes {
+        Some(path) => Some(ChangeLog::create(
+            path,
+            &mut payment_engine,
+            args.output.display(),
+        )?),
+        None => None,
+    };
 
-    for csv_record in csv_stream(buffer) {
-        match csv_record {
-            Ok(record) => {
-                if let Err(err) = payment_engine.process_csv_record(record) {
-                    let _ = writeln!(stderr, "Error processing Transaction due to {err:?}");
+    args.input
+        .for_each_record(&inputs, start.as_ref(), |at, batch, record| {
+            let (input, n) = (at.input, at.n);
+            let batch = batch.filter(|_| args.batch_policy == BatchPolicy::Rollback);
+            records += 1;
+            // A batch ends at the first record outside it
+            if let Some(ended) =
+                pending.take_if(|p| &p.input != input || Some(p.id.as_str()) != batch)
+            {
+                ended.apply(&mut payment_engine, format, &mut stderr);
+            }
+            match batch {
+                Some(id) => pending
+                    .get_or_insert_with(|| PendingBatch::new(input, id, records as u64 - 1))
+                    .push(n, record),
+                None => {
+                    // Keep positions equal to record numbers across all inputs for `--as-of`
+                    payment_engine.set_position(records as u64 - 1);
+                    if let Err(err) =
+                        record.and_then(|record| payment_engine.process_csv_record(record))
+                    {
+                        args.input.report(&mut stderr, input, n, &err);
+                    }
                 }
             }
+            if let Some(changes) = &mut changes {
+                changes.drain()?;
+            }
+            // Checkpoints fall between batches so a resume never starts inside one
+            match pending {
+                Some(_) => Ok(()),
+                None => {
+                    let position = || InputPosition {
+                        inputs: fingerprints.clone(),
+                        input: at.index,
+                        records: at.n,
+                        offset: at.offset,
+                    };
+                    args.checkpoint.save_every(
+                        &mut writer,
+                        records,
+                        &mut saved,
+                        position,
+                        &mut payment_engine,
+                    )
+                }
+            }
+        })?;
+    if let Some(batch) = pending {
+        batch.apply(&mut payment_engine, format, &mut stderr);
+    }
+    if let Some(mut changes) = changes {
+        changes.drain()?;
+        changes.wtr.finish()?;
+    }
+
+    Ok(payment_engine)
+}
+
+fn process(args: &ProcessArgs) -> Result<ExitCode, CliError> {
+    let payment_engine = run_engine(args)?;
+    let mut record_wtr = RecordWriter::new(args.output.format, io::stdout().lock());
+    for account in args.accounts(&payment_engine)? {
+        record_wtr.write(&account)?;
+    }
+    record_wtr.finish()?;
+    Ok(ExitCode::SUCCESS)
+}
+
+fn validate(args: &InputArgs) -> Result<ExitCode, CliError> {
+    // A single validator so TxId uniqueness is checked across every input
+    let mut validator = Validator::default();
+    let mut stderr = io::stderr().lock();
+    let mut failures = 0usize;
+
+    // Unlike `process`, a malformed row is one more finding rather than the end of the run
+    args.for_each_row(&args.resolve()?, None, |at, row| {
+        let (input, n) = (at.input, at.n);
+        let record = match row {
+            Ok(record) => record,
             Err(err) => {
-                let _ = writeln!(stderr, "Error reading csv");
-                return Err(err);
+                failures += 1;
+                args.report_malformed(&mut stderr, input, n, &err);
+                return Ok(());
             }
+        };
+        if let Err(err) = check_strict(record).and_then(|record| validator.check(&record)) {
+            failures += 1;
+            args.report(&mut stderr, input, n, &err);
         }
+        Ok(())
+    })?;
+
+    let _ = writeln!(stderr, "{failures} invalid record(s)");
+    Ok(if failures == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_FINDINGS)
+    })
+}
+
+fn inspect(args: &ProcessArgs, client: Option<u16>, tx: Option<u32>) -> Result<ExitCode, CliError> {
+    let payment_engine = run_engine(args)?;
+    let mut record_wtr = RecordWriter::new(args.output.format, io::stdout().lock());
+
+    let found = match (client, tx) {
+        (Some(client), _) => args
+            .accounts(&payment_engine)?
+            .into_iter()
+            .find(|account| account.client == client.into())
+            .map(|account| record_wtr.write(&account))
+            .transpose()?
+            .is_some(),
+        (None, Some(tx)) => payment_engine
+            .tx_manager
+            .get(tx.into())
+            .map(|transaction| record_wtr.write(transaction))
+            .transpose()?
+            .is_some(),
+        (None, None) => unreachable!("clap requires --client or --tx"),
+    };
+    record_wtr.finish()?;
+
+    if found {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        let _ = writeln!(io::stderr(), "not found");
+        Ok(ExitCode::from(EXIT_FINDINGS))
+    }
+}
+
+fn statement(
+    args: &ProcessArgs,
+    client: u16,
+    from: u64,
+    to: Option<u64>,
+    text: bool,
+) -> Result<ExitCode, CliError> {
+    let payment_engine = run_engine(args)?;
+    if !payment_engine.client_manager.exists(client.into()) {
+        let _ = writeln!(io::stderr(), "not found");
+        return Ok(ExitCode::from(EXIT_FINDINGS));
+    }
+
+    let display = args.output.display();
+    let to = to.unwrap_or_else(|| payment_engine.position());
+    let statement = Statement::build(&payment_engine.journal, client.into(), from, to);
+    if text {
+        statement
+            .write_text(&display, io::stdout().lock())
+            .map_err(|err| CliError::Output(err.into()))?;
+    } else {
+        let mut record_wtr = RecordWriter::new(args.output.format, io::stdout().lock());
+        for row in statement.rows(&display) {
+            record_wtr.write(&row)?;
+        }
+        record_wtr.finish()?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn diff(left: &Path, right: &Path) -> Result<ExitCode, CliError> {
+    let index = |accounts: Vec<OutputRecord>| {
+        accounts
+            .into_iter()
+            .map(|account| (account.client, account))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let read = |path: &Path| {
+        let input = Input::File(path.to_path_buf());
+        read_accounts(open(&input)?).map_err(|source| CliError::Parse {
+            input,
+            source: source.into(),
+        })
+    };
+    let left_accounts = index(read(left)?);
+    let mut right_accounts = index(read(right)?);
+    let mut stdout = io::stdout().lock();
+    let mut differences = 0usize;
+
+    for (client, l) in left_accounts {
+        let line = match right_accounts.remove(&client) {
+            None => format!("client {client}: only in {}", left.display()),
+            Some(r) if l != r => format!(
+                "client {client}: available {} -> {}, held {} -> {}, total {} -> {}, locked {} -> {}",
+                l.available, r.available, l.held, r.held, l.total, r.total, l.locked, r.locked
+            ),
+            Some(_) => continue,
+        };
+        differences += 1;
+        let _ = writeln!(stdout, "{line}");
+    }
+    for client in right_accounts.into_keys() {
+        differences += 1;
+        let _ = writeln!(stdout, "client {client}: only in {}", right.display());
     }
 
-    write_accounts_to_stdout(&payment_engine.client_manager)
+    Ok(if differences == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_FINDINGS)
+    })
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match &cli.command {
+        None => process(&cli.process),
+        Some(Command::Process(input)) => process(input),
+        Some(Command::Validate(input)) => validate(input),
+        Some(Command::Inspect { args, client, tx }) => inspect(args, *client, *tx),
+        Some(Command::Diff { left, right }) => diff(left, right),
+        Some(Command::Statement {
+            args,
+            client,
+            from,
+            to,
+            text,
+        }) => statement(args, *client, *from, *to, *text),
+        Some(Command::Follow(args)) => follow::follow(args),
+        Some(Command::Watch(args)) => watch::watch(args),
+    };
+
+    result.unwrap_or_else(|err| {
+        let _ = writeln!(io::stderr(), "{err}");
+        err.exit_code()
+    })
 }
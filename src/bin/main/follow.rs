@@ -0,0 +1,259 @@
+//! `follow`: applies lines as they are appended to a CSV file, like `tail -f`.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+use silhouette::{
+    checkpoint::{Checkpoint, CheckpointWriter, InputPosition},
+    file_reader::raw_csv_stream,
+    ledger::engine::PaymentsEngine,
+    output::write_accounts,
+    validation::check_strict,
+};
+
+use crate::{CliError, EngineArgs, ErrorFormat, Input, OutputArgs, report, report_malformed};
+
+#[derive(Args)]
+pub struct FollowArgs {
+    /// CSV file to follow; it must start with a header line
+    input: PathBuf,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// Resume from this checkpoint if it exists, and keep it up to date
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+    /// Save the checkpoint at most every this many seconds while new lines arrive, and on exit
+    #[arg(long, default_value_t = 5, requires = "checkpoint")]
+    checkpoint_interval: u64,
+    /// How often to look for new lines, in milliseconds
+    #[arg(long, default_value_t = 250)]
+    poll_interval: u64,
+    /// Also dump the accounts to stdout every this many seconds
+    #[arg(long)]
+    dump_interval: Option<u64>,
+    /// Format of the rejection lines written to stderr
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+}
+
+/// Flags raised by SIGUSR1 (dump the accounts) and SIGINT/SIGTERM (dump them and stop).
+#[derive(Default)]
+struct Signals {
+    dump: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Signals {
+    fn register() -> io::Result<Self> {
+        let signals = Self::default();
+        #[cfg(unix)]
+        {
+            use signal_hook::{consts, flag};
+            flag::register(consts::SIGUSR1, Arc::clone(&signals.dump))?;
+            flag::register(consts::SIGINT, Arc::clone(&signals.stop))?;
+            flag::register(consts::SIGTERM, Arc::clone(&signals.stop))?;
+        }
+        Ok(signals)
+    }
+}
+
+/// The most read from the input at once. A longer line is still read whole.
+const CHUNK_SIZE: u64 = 1 << 20;
+
+/// The input file, read up to the last complete line so a half-written record is never parsed.
+struct Tail {
+    file: File,
+    header: Vec<u8>,
+    offset: u64,
+}
+
+impl Tail {
+    /// Waits for the header line, then positions after it or at a checkpointed `offset`. Returns
+    /// `None` if `stop` is raised while waiting.
+    fn open(
+        input: &Input,
+        path: &Path,
+        offset: u64,
+        poll: Duration,
+        stop: &AtomicBool,
+    ) -> Result<Option<Self>, CliError> {
+        let open_err = |source| CliError::Open {
+            input: input.clone(),
+            source,
+        };
+        let mut tail = Tail {
+            file: File::open(path).map_err(open_err)?,
+            header: Vec::new(),
+            offset: 0,
+        };
+        while tail.header.is_empty() {
+            tail.header = tail.complete_lines().map_err(open_err)?;
+            tail.header.truncate(
+                tail.header
+                    .iter()
+                    .position(|b| *b == b'\n')
+                    .map_or(0, |i| i + 1),
+            );
+            if tail.header.is_empty() {
+                if stop.load(Ordering::Relaxed) {
+                    return Ok(None);
+                }
+                thread::sleep(poll);
+            }
+        }
+        tail.offset = offset.max(tail.header.len() as u64);
+        Ok(Some(tail))
+    }
+
+    /// The complete lines from `offset` within the next [`CHUNK_SIZE`] bytes, or the first line
+    /// if it is longer.
+    fn complete_lines(&mut self) -> io::Result<Vec<u8>> {
+        if self.file.metadata()?.len() < self.offset {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "input was truncated below the followed position",
+            ));
+        }
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let mut reader = BufReader::new(&mut self.file);
+        let mut buf = Vec::new();
+        loop {
+            let read = (&mut reader).take(CHUNK_SIZE).read_to_end(&mut buf)?;
+            if (read as u64) < CHUNK_SIZE || buf[buf.len() - read..].contains(&b'\n') {
+                break;
+            }
+        }
+        buf.truncate(buf.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1));
+        Ok(buf)
+    }
+}
+
+fn dump(args: &FollowArgs, engine: &PaymentsEngine) -> Result<(), CliError> {
+    let mut stdout = io::stdout().lock();
+    write_accounts(
+        &engine.client_manager,
+        args.output.format,
+        &args.output.display(),
+        &mut stdout,
+    )?;
+    stdout.flush().map_err(|err| CliError::Output(err.into()))
+}
+
+pub fn follow(args: &FollowArgs) -> Result<std::process::ExitCode, CliError> {
+    let input = Input::File(args.input.clone());
+    let poll = Duration::from_millis(args.poll_interval);
+    let precision = args.engine.precision();
+
+    let checkpoint = match &args.checkpoint {
+        Some(path) => Checkpoint::load(path, precision).map_err(|source| CliError::Open {
+            input: Input::File(path.clone()),
+            source,
+        })?,
+        None => None,
+    };
+    let (mut engine, offset, mut records) = match checkpoint {
+        Some(checkpoint) => {
+            // A rotated or truncated file would be resumed mid-line at a stale offset
+            let current = input.fingerprint()?;
+            let followed = checkpoint
+                .input_position
+                .and_then(|position| position.inputs.into_iter().next());
+            if !followed.is_some_and(|followed| followed.grew_into(&current)) {
+                return Err(CliError::FollowMismatch(input));
+            }
+            (checkpoint.engine, checkpoint.offset, checkpoint.records)
+        }
+        None => (PaymentsEngine::with_precision(precision), 0, 0),
+    };
+
+    let signals = Signals::register().map_err(|source| CliError::Open {
+        input: input.clone(),
+        source,
+    })?;
+    let Some(mut tail) = Tail::open(&input, &args.input, offset, poll, &signals.stop)? else {
+        dump(args, &engine)?;
+        return Ok(std::process::ExitCode::SUCCESS);
+    };
+    let mut stderr = io::stderr().lock();
+    let mut last_dump = Instant::now();
+    let mut last_save = Instant::now();
+    let mut unsaved = false;
+    let mut writer = args.checkpoint.as_deref().map(CheckpointWriter::new);
+    let mut save = |tail: &Tail, records, engine: &mut PaymentsEngine| match &mut writer {
+        Some(writer) => {
+            let position = InputPosition {
+                inputs: vec![input.fingerprint()?],
+                input: 0,
+                records,
+                offset: Some(tail.offset),
+            };
+            writer
+                .save(tail.offset, records, Some(&position), engine)
+                .map_err(|err| CliError::Output(err.into()))
+        }
+        None => Ok(()),
+    };
+
+    loop {
+        let chunk = tail.complete_lines().map_err(|source| CliError::Parse {
+            input: input.clone(),
+            source: source.into(),
+        })?;
+        if !chunk.is_empty() {
+            for record in raw_csv_stream(tail.header.as_slice().chain(chunk.as_slice())) {
+                records += 1;
+                let result = match record {
+                    Ok(record) => {
+                        check_strict(record).and_then(|record| engine.process_csv_record(record))
+                    }
+                    Err(err) => {
+                        let err = err.into();
+                        report_malformed(&mut stderr, args.error_format, &input, records, &err);
+                        continue;
+                    }
+                };
+                if let Err(err) = result {
+                    report(&mut stderr, args.error_format, &input, records, None, &err);
+                }
+            }
+            tail.offset += chunk.len() as u64;
+            unsaved = true;
+        }
+
+        let stop = signals.stop.load(Ordering::Relaxed);
+        let save_due = last_save.elapsed() >= Duration::from_secs(args.checkpoint_interval);
+        if unsaved && (save_due || stop) {
+            save(&tail, records, &mut engine)?;
+            last_save = Instant::now();
+            unsaved = false;
+        }
+        if stop {
+            dump(args, &engine)?;
+            return Ok(std::process::ExitCode::SUCCESS);
+        }
+        let interval_elapsed = args
+            .dump_interval
+            .is_some_and(|secs| last_dump.elapsed() >= Duration::from_secs(secs));
+        if signals.dump.swap(false, Ordering::Relaxed) || interval_elapsed {
+            dump(args, &engine)?;
+            last_dump = Instant::now();
+        }
+
+        // A backlog is read chunk after chunk; only an idle file is polled
+        if chunk.is_empty() {
+            thread::sleep(poll);
+        }
+    }
+}
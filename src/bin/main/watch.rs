@@ -0,0 +1,414 @@
+//! `watch`: processes files dropped into an inbox directory, in filename order.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use silhouette::{
+    checkpoint::StateLog,
+    file_reader::{InputFormat, raw_record_stream},
+    ledger::engine::{EngineChanges, PaymentsEngine},
+    output::{OutputFormat, write_accounts},
+    precision::PrecisionPolicy,
+    transaction::TransactionError,
+    validation::check_strict,
+};
+
+use crate::{CliError, EngineArgs, Input, OutputArgs, Rejection, open};
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Directory new transaction files are dropped into. Processed files move to its `done/` or
+    /// `failed/` subdirectory
+    inbox: PathBuf,
+    /// Directory for the per-file account snapshots and rejection reports, and the manifest
+    #[arg(long)]
+    outbox: PathBuf,
+    #[command(flatten)]
+    engine: EngineArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+    /// How often to look for new files, in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    poll_interval: u64,
+    /// Process the files currently in the inbox, then exit
+    #[arg(long)]
+    once: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum FileStatus {
+    Done,
+    Failed,
+}
+
+impl FileStatus {
+    fn dir(self) -> &'static str {
+        match self {
+            FileStatus::Done => "done",
+            FileStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct ManifestEntry {
+    file: String,
+    /// The name the file was archived and reported under, when `file` was already taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stored_as: Option<String>,
+    sha256: String,
+    status: FileStatus,
+    records: usize,
+    rejections: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The file being processed, saved before it is applied and cleared once it has been moved out
+/// of the inbox.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct InProgress {
+    file: String,
+    sha256: String,
+}
+
+/// The checksum manifest and the engine are saved together, so a crash can never record a file
+/// as processed without also keeping its effect on the accounts, or the reverse.
+#[derive(Default, Serialize, Deserialize)]
+struct WatchState {
+    manifest: Vec<ManifestEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    in_progress: Option<InProgress>,
+    engine: PaymentsEngine,
+}
+
+/// A later save of the state, as what changed since the one before.
+#[derive(Serialize, Deserialize)]
+struct WatchChanges {
+    manifest: Vec<ManifestEntry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    in_progress: Option<InProgress>,
+    engine: EngineChanges,
+}
+
+struct Watcher<'a> {
+    args: &'a WatchArgs,
+    state_path: PathBuf,
+    log: StateLog,
+    precision: PrecisionPolicy,
+    state: WatchState,
+    /// Manifest entries already saved.
+    saved_manifest: usize,
+}
+
+fn io_err(path: &Path) -> impl FnOnce(io::Error) -> CliError {
+    let input = Input::File(path.to_path_buf());
+    move |source| CliError::Open { input, source }
+}
+
+fn sha256(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0; 64 * 1024];
+    loop {
+        match file.read(&mut buf)? {
+            0 => break,
+            n => hasher.update(&buf[..n]),
+        }
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+fn extension(format: OutputFormat) -> &'static str {
+    match format {
+        OutputFormat::Csv => "csv",
+        OutputFormat::Json => "json",
+        OutputFormat::JsonLines => "jsonl",
+    }
+}
+
+impl ManifestEntry {
+    fn stored_name(&self) -> &str {
+        self.stored_as.as_deref().unwrap_or(&self.file)
+    }
+}
+
+impl Watcher<'_> {
+    /// The last saved state, or a fresh engine on the first run.
+    fn load_state(&self) -> Result<WatchState, CliError> {
+        let loaded = StateLog::load::<WatchState, WatchChanges>(&self.state_path)
+            .map_err(io_err(&self.state_path))?;
+        let mut state = match loaded {
+            Some((mut state, changes)) => {
+                for changes in changes {
+                    state.manifest.extend(changes.manifest);
+                    state.in_progress = changes.in_progress;
+                    state.engine.apply_changes(changes.engine);
+                }
+                state
+            }
+            None => WatchState::default(),
+        };
+        state.engine.set_precision(self.precision);
+        Ok(state)
+    }
+
+    /// Files waiting in the inbox, in filename order. Dotfiles are ignored so producers can
+    /// write under a hidden name and rename once complete.
+    fn pending(&self) -> Result<Vec<PathBuf>, CliError> {
+        let inbox = &self.args.inbox;
+        let mut files = fs::read_dir(inbox)
+            .map_err(io_err(inbox))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter(|path| !path.file_name().unwrap().to_string_lossy().starts_with('.'))
+            .collect::<Vec<_>>();
+        files.sort();
+        Ok(files)
+    }
+
+    /// Applies every record in `path`, collecting rejections. A malformed file is an error, and
+    /// everything applied from it is rolled back.
+    fn apply(&mut self, path: &Path) -> Result<(usize, Vec<(usize, TransactionError)>), CliError> {
+        self.state.engine.begin();
+        let applied = self.apply_records(path);
+        match applied {
+            Ok(_) => self.state.engine.commit(),
+            Err(_) => self.state.engine.roll_back(),
+        }
+        applied
+    }
+
+    fn apply_records(
+        &mut self,
+        path: &Path,
+    ) -> Result<(usize, Vec<(usize, TransactionError)>), CliError> {
+        let input = Input::File(path.to_path_buf());
+        let parse_err = |source| CliError::Parse {
+            input: input.clone(),
+            source,
+        };
+        let records = raw_record_stream(open(&input)?, InputFormat::from_extension(path), None)
+            .map_err(parse_err)?;
+
+        let mut count = 0;
+        let mut rejections = Vec::new();
+        for (idx, record) in records.enumerate() {
+            let record = record.map_err(parse_err)?;
+            count += 1;
+            let result = check_strict(record)
+                .and_then(|record| self.state.engine.process_csv_record(record));
+            if let Err(err) = result {
+                rejections.push((idx + 1, err));
+            }
+        }
+        Ok((count, rejections))
+    }
+
+    /// `name`, or if an earlier file already used it, `name` prefixed with the start of the
+    /// file's SHA-256 (and a counter if need be), so archives and reports are never overwritten.
+    fn unique_name(&self, name: &str, sha256: &str) -> String {
+        let taken = |candidate: &str| {
+            self.state
+                .manifest
+                .iter()
+                .any(|entry| entry.stored_name() == candidate)
+                || [FileStatus::Done, FileStatus::Failed]
+                    .iter()
+                    .any(|status| self.args.inbox.join(status.dir()).join(candidate).exists())
+        };
+        let prefix = &sha256[..12];
+        std::iter::once(name.to_owned())
+            .chain(std::iter::once(format!("{prefix}-{name}")))
+            .chain((2..).map(|n| format!("{prefix}-{n}-{name}")))
+            .find(|candidate| !taken(candidate))
+            .unwrap()
+    }
+
+    fn write_outbox(
+        &self,
+        name: &str,
+        rejections: &[(usize, TransactionError)],
+    ) -> Result<(), CliError> {
+        let outbox = &self.args.outbox;
+        let path = outbox.join(format!("{name}.rejections.jsonl"));
+        let mut wtr = BufWriter::new(File::create(&path).map_err(io_err(&path))?);
+        for (record, error) in rejections {
+            let rejection = Rejection {
+                input: name.to_owned(),
+                record: *record,
+                batch: None,
+                error,
+            };
+            serde_json::to_writer(&mut wtr, &rejection)
+                .map_err(|err| CliError::Output(err.into()))?;
+            writeln!(wtr).map_err(|err| CliError::Output(err.into()))?;
+        }
+        wtr.flush().map_err(|err| CliError::Output(err.into()))?;
+
+        let format = self.args.output.format;
+        let path = outbox.join(format!("{name}.accounts.{}", extension(format)));
+        write_accounts(
+            &self.state.engine.client_manager,
+            format,
+            &self.args.output.display(),
+            File::create(&path).map_err(io_err(&path))?,
+        )?;
+        Ok(())
+    }
+
+    fn save(&mut self) -> Result<(), CliError> {
+        let changes = self.state.engine.take_changes().map(|engine| WatchChanges {
+            manifest: self.state.manifest[self.saved_manifest..].to_vec(),
+            in_progress: self.state.in_progress.clone(),
+            engine,
+        });
+        let saved_state =
+            (self.log.save(&self.state, changes)).map_err(|err| CliError::Output(err.into()))?;
+        if saved_state {
+            self.state.engine.track_changes();
+        }
+        self.saved_manifest = self.state.manifest.len();
+        Ok(())
+    }
+
+    /// Moves `path` into `done/` or `failed/` as `stored`.
+    fn finish(&self, path: &Path, stored: &str, status: FileStatus) -> Result<(), CliError> {
+        let to = self.args.inbox.join(status.dir()).join(stored);
+        fs::rename(path, &to).map_err(io_err(path))
+    }
+
+    /// [`finish`](Self::finish), then clears the in-progress marker.
+    fn finish_in_progress(
+        &mut self,
+        path: &Path,
+        stored: &str,
+        status: FileStatus,
+    ) -> Result<(), CliError> {
+        self.finish(path, stored, status)?;
+        self.state.in_progress = None;
+        self.save()
+    }
+
+    fn process(&mut self, path: &Path) -> Result<(), CliError> {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let sha256 = sha256(path).map_err(io_err(path))?;
+        let mut stderr = io::stderr().lock();
+
+        let marker = InProgress {
+            file: name.clone(),
+            sha256: sha256.clone(),
+        };
+        if let Some(entry) = self
+            .state
+            .manifest
+            .iter()
+            .find(|entry| entry.sha256 == sha256)
+        {
+            // Only the marker tells a previous run that stopped between saving and moving this
+            // file apart from the same file dropped again
+            if self.state.in_progress.as_ref() == Some(&marker) {
+                let (stored, status) = (entry.stored_name().to_owned(), entry.status);
+                let _ = writeln!(stderr, "{name}: recovered, moved to {}/", status.dir());
+                return self.finish_in_progress(path, &stored, status);
+            }
+            let _ = writeln!(
+                stderr,
+                "{name}: skipped, already processed as {}",
+                entry.file
+            );
+            let stored = self.unique_name(&name, &sha256);
+            return self.finish(path, &stored, FileStatus::Failed);
+        }
+
+        self.state.in_progress = Some(marker);
+        self.save()?;
+        let stored = self.unique_name(&name, &sha256);
+        if stored != name {
+            let _ = writeln!(stderr, "{name}: name already used, stored as {stored}");
+        }
+        let stored_as = (stored != name).then(|| stored.clone());
+        let entry = match self.apply(path) {
+            Ok((records, rejections)) => {
+                self.write_outbox(&stored, &rejections)?;
+                let _ = writeln!(
+                    stderr,
+                    "{name}: done, {records} record(s), {} rejected",
+                    rejections.len()
+                );
+                ManifestEntry {
+                    file: name,
+                    stored_as,
+                    sha256,
+                    status: FileStatus::Done,
+                    records,
+                    rejections: rejections.len(),
+                    error: None,
+                }
+            }
+            Err(err) => {
+                let _ = writeln!(stderr, "{name}: failed, {err}");
+                ManifestEntry {
+                    file: name,
+                    stored_as,
+                    sha256,
+                    status: FileStatus::Failed,
+                    records: 0,
+                    rejections: 0,
+                    error: Some(err.to_string()),
+                }
+            }
+        };
+
+        let status = entry.status;
+        self.state.manifest.push(entry);
+        self.save()?;
+        self.finish_in_progress(path, &stored, status)
+    }
+}
+
+pub fn watch(args: &WatchArgs) -> Result<ExitCode, CliError> {
+    for dir in [
+        args.inbox.join(FileStatus::Done.dir()),
+        args.inbox.join(FileStatus::Failed.dir()),
+        args.outbox.clone(),
+    ] {
+        fs::create_dir_all(&dir).map_err(io_err(&dir))?;
+    }
+
+    let state_path = args.outbox.join("state.json");
+    let mut watcher = Watcher {
+        args,
+        log: StateLog::new(&state_path),
+        state_path,
+        precision: args.engine.precision(),
+        state: WatchState::default(),
+        saved_manifest: 0,
+    };
+    watcher.state = watcher.load_state()?;
+
+    loop {
+        for path in watcher.pending()? {
+            watcher.process(&path)?;
+        }
+        if args.once {
+            return Ok(ExitCode::SUCCESS);
+        }
+        thread::sleep(Duration::from_millis(args.poll_interval));
+    }
+}
@@ -0,0 +1,83 @@
+use std::{io, net::SocketAddr, path::PathBuf};
+
+use bigdecimal::RoundingMode;
+use clap::{Parser, value_parser};
+use silhouette::{
+    ingest,
+    ledger::engine::PaymentsEngine,
+    precision::{MAX_SCALE, PrecisionPolicy, parse_rounding_mode},
+    server::{EngineHandle, serve},
+};
+use tokio::{net::TcpListener, task::JoinSet};
+
+/// Serves a single `PaymentsEngine` over HTTP, optionally also ingesting CSV lines from sockets.
+#[derive(Parser)]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+    /// Also accept newline-delimited CSV records on this TCP address
+    #[arg(long)]
+    ingest_tcp: Option<SocketAddr>,
+    /// Also accept newline-delimited CSV records on this Unix domain socket, which must not exist
+    #[cfg(unix)]
+    #[arg(long)]
+    ingest_unix: Option<PathBuf>,
+    /// Decimal places amounts are held to, at most 28
+    #[arg(long, default_value_t = 4, value_parser = value_parser!(u32).range(0..=MAX_SCALE))]
+    scale: u32,
+    /// Rounding mode for amounts with more decimal places than `--scale`
+    #[arg(long, default_value = "half-even", value_parser = parse_rounding_mode)]
+    rounding: RoundingMode,
+    /// Reject amounts with more decimal places than `--scale` instead of rounding them
+    #[arg(long)]
+    reject_excess_precision: bool,
+}
+
+fn report(err: ingest::IngestError) {
+    eprintln!("{err}");
+}
+
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    let args = Args::parse();
+    let engine = PaymentsEngine::with_precision(PrecisionPolicy {
+        scale: args.scale.into(),
+        rounding: args.rounding,
+        reject_excess: args.reject_excess_precision,
+    });
+
+    let handle = EngineHandle::spawn(engine);
+    let mut tasks = JoinSet::new();
+
+    let listener = TcpListener::bind(args.listen).await?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    tasks.spawn(serve(listener, handle.clone()));
+
+    if let Some(addr) = args.ingest_tcp {
+        let listener = TcpListener::bind(addr).await?;
+        eprintln!("ingesting on {}", listener.local_addr()?);
+        let handle = handle.clone();
+        tasks.spawn(async move {
+            ingest::serve_tcp(listener, handle, report).await;
+            Ok(())
+        });
+    }
+    #[cfg(unix)]
+    if let Some(path) = &args.ingest_unix {
+        let listener = tokio::net::UnixListener::bind(path)?;
+        eprintln!("ingesting on {}", path.display());
+        let handle = handle.clone();
+        tasks.spawn(async move {
+            ingest::serve_unix(listener, handle, report).await;
+            Ok(())
+        });
+    }
+
+    // Every task runs until the process exits, so the first to finish has failed. Ingestion
+    // reports its own errors and never finishes
+    match tasks.join_next().await {
+        Some(result) => result.map_err(io::Error::other)?,
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,427 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    ledger::engine::{EngineChanges, PaymentsEngine},
+    precision::PrecisionPolicy,
+};
+
+/// Identifies an input by path, size, modification time and inode, so a resume can tell
+/// whether it is still the input the checkpoint was taken over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputFingerprint {
+    pub input: String,
+    /// `None` for stdin, which cannot be checked.
+    pub len: Option<u64>,
+    pub modified_ns: Option<u64>,
+    /// `None` off unix.
+    #[serde(default)]
+    pub inode: Option<u64>,
+}
+
+impl InputFingerprint {
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        let modified_ns = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|since| since.as_nanos() as u64);
+        #[cfg(unix)]
+        let inode = Some(std::os::unix::fs::MetadataExt::ino(&metadata));
+        #[cfg(not(unix))]
+        let inode = None;
+        Ok(Self {
+            input: path.display().to_string(),
+            len: Some(metadata.len()),
+            modified_ns,
+            inode,
+        })
+    }
+
+    pub fn stdin() -> Self {
+        Self {
+            input: "-".to_owned(),
+            len: None,
+            modified_ns: None,
+            inode: None,
+        }
+    }
+
+    /// Whether `current` is this input, at most grown since: the same file, no shorter and
+    /// modified no earlier. A followed file grows, so it cannot be compared for equality.
+    pub fn grew_into(&self, current: &InputFingerprint) -> bool {
+        self.input == current.input
+            && self.inode == current.inode
+            && self.len <= current.len
+            && self.modified_ns <= current.modified_ns
+    }
+}
+
+/// Where a run over a list of inputs stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputPosition {
+    /// Every input of the run, in order.
+    pub inputs: Vec<InputFingerprint>,
+    /// Index of the input the next record comes from.
+    pub input: usize,
+    /// Records already read from that input.
+    pub records: usize,
+    /// Byte offset of the next record in that input, when it is an uncompressed file. Other
+    /// inputs are read again from the start, skipping `records`.
+    pub offset: Option<u64>,
+}
+
+/// A position in the input together with the engine state after applying everything before it,
+/// so processing can resume there as if it had never stopped.
+#[derive(Deserialize)]
+pub struct Checkpoint {
+    /// Byte offset of the first record not yet applied in a followed file. Runs over a list of
+    /// inputs use `input_position` instead; a followed file keeps its fingerprint there.
+    pub offset: u64,
+    /// Records applied so far, used to keep record numbers in reports stable across restarts.
+    pub records: usize,
+    #[serde(default)]
+    pub input_position: Option<InputPosition>,
+    pub engine: PaymentsEngine,
+}
+
+#[derive(Serialize)]
+struct CheckpointRef<'a> {
+    offset: u64,
+    records: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input_position: Option<&'a InputPosition>,
+    engine: &'a PaymentsEngine,
+}
+
+/// A later checkpoint, as what changed since the one before.
+#[derive(Serialize, Deserialize)]
+struct CheckpointChanges {
+    offset: u64,
+    records: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    input_position: Option<InputPosition>,
+    engine: EngineChanges,
+}
+
+impl Checkpoint {
+    /// Reads the checkpoint at `path`, or `None` if there is none yet. The precision policy is
+    /// configuration, so it is supplied again rather than restored.
+    pub fn load(path: &Path, precision: PrecisionPolicy) -> io::Result<Option<Self>> {
+        let Some((mut checkpoint, changes)) =
+            StateLog::load::<Checkpoint, CheckpointChanges>(path)?
+        else {
+            return Ok(None);
+        };
+        for changes in changes {
+            checkpoint.offset = changes.offset;
+            checkpoint.records = changes.records;
+            checkpoint.input_position = changes.input_position;
+            checkpoint.engine.apply_changes(changes.engine);
+        }
+        checkpoint.engine.set_precision(precision);
+        Ok(Some(checkpoint))
+    }
+}
+
+/// Saves checkpoints at one path through a [`StateLog`], so each save after the first only
+/// writes what the engine changed.
+pub struct CheckpointWriter {
+    log: StateLog,
+}
+
+impl CheckpointWriter {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            log: StateLog::new(path),
+        }
+    }
+
+    /// Saves a checkpoint, which from then on tracks `engine`'s changes.
+    pub fn save(
+        &mut self,
+        offset: u64,
+        records: usize,
+        input_position: Option<&InputPosition>,
+        engine: &mut PaymentsEngine,
+    ) -> io::Result<()> {
+        let changes = engine.take_changes().map(|engine| CheckpointChanges {
+            offset,
+            records,
+            input_position: input_position.cloned(),
+            engine,
+        });
+        let state = CheckpointRef {
+            offset,
+            records,
+            input_position,
+            engine,
+        };
+        if self.log.save(&state, changes)? {
+            engine.track_changes();
+        }
+        Ok(())
+    }
+}
+
+/// State saved in full to a base file, then as changes appended to a log beside it, so a save
+/// costs what changed rather than everything accumulated. The base is saved again, emptying the
+/// log, on the first save of each run and whenever the log has grown larger than it.
+pub struct StateLog {
+    path: PathBuf,
+    /// Tags the base and each change, so a log left over from an older base is ignored.
+    id: u64,
+    log: Option<File>,
+    base_len: u64,
+    log_len: u64,
+}
+
+#[derive(Serialize)]
+struct BaseRef<'a, T> {
+    log: u64,
+    state: &'a T,
+}
+
+#[derive(Deserialize)]
+struct Base<T> {
+    log: u64,
+    state: T,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Logged<D> {
+    log: u64,
+    changes: D,
+}
+
+fn log_path(path: &Path) -> PathBuf {
+    let mut log = OsString::from(path.as_os_str());
+    log.push(".log");
+    log.into()
+}
+
+impl StateLog {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            id: 0,
+            log: None,
+            base_len: 0,
+            log_len: 0,
+        }
+    }
+
+    /// Reads the base at `path` and the changes logged against it in order, or `None` if
+    /// nothing was saved yet. A change left incomplete by a crash ends the log.
+    pub fn load<T: DeserializeOwned, D: DeserializeOwned>(
+        path: &Path,
+    ) -> io::Result<Option<(T, Vec<D>)>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let base: Base<T> = serde_json::from_reader(BufReader::new(file))?;
+        let log = match File::open(log_path(path)) {
+            Ok(log) => log,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(Some((base.state, vec![])));
+            }
+            Err(err) => return Err(err),
+        };
+        let mut changes = Vec::new();
+        for line in BufReader::new(log).split(b'\n') {
+            let Ok(logged) = serde_json::from_slice::<Logged<D>>(&line?) else {
+                break;
+            };
+            if logged.log == base.log {
+                changes.push(logged.changes);
+            }
+        }
+        Ok(Some((base.state, changes)))
+    }
+
+    /// Appends `changes`, or saves `state` in full instead if there are none to append, this is
+    /// the first save, or the log has outgrown the base. Returns whether `state` was saved, in
+    /// which case the next changes are to be taken from it.
+    pub fn save<T: Serialize, D: Serialize>(
+        &mut self,
+        state: &T,
+        changes: Option<D>,
+    ) -> io::Result<bool> {
+        match (&mut self.log, changes) {
+            (Some(log), Some(changes)) if self.log_len <= self.base_len => {
+                let mut line = serde_json::to_vec(&Logged {
+                    log: self.id,
+                    changes,
+                })?;
+                line.push(b'\n');
+                log.write_all(&line)?;
+                log.sync_data()?;
+                self.log_len += line.len() as u64;
+                Ok(false)
+            }
+            _ => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_nanos() as u64);
+                let id = now.max(self.id + 1);
+                write_json_atomically(&self.path, &BaseRef { log: id, state })?;
+                let log = File::create(log_path(&self.path))?;
+                log.sync_all()?;
+                self.base_len = fs::metadata(&self.path)?.len();
+                self.log_len = 0;
+                self.id = id;
+                self.log = Some(log);
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Writes `value` beside `path` and renames it into place, so a crash mid-write leaves the
+/// previous contents intact.
+pub fn write_json_atomically<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut wtr = BufWriter::new(File::create(&tmp)?);
+    serde_json::to_writer(&mut wtr, value)?;
+    wtr.into_inner()
+        .map_err(io::IntoInnerError::into_error)?
+        .sync_all()?;
+    fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        checkpoint::{Checkpoint, CheckpointWriter},
+        file_reader::csv_stream,
+        ledger::engine::PaymentsEngine,
+        output::output_records,
+        precision::PrecisionPolicy,
+    };
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let test_data = r#"type,client,tx,amount
+deposit,1,1,10.5
+deposit,2,2,3
+dispute,1,1,
+withdrawal,2,3,1.25
+"#;
+        let mut payment_engine = PaymentsEngine::default();
+        for record in csv_stream(test_data.as_bytes()) {
+            payment_engine.process_csv_record(record.unwrap()).unwrap();
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("silhouette-checkpoint-{}.json", std::process::id()));
+        CheckpointWriter::new(&path)
+            .save(42, 4, None, &mut payment_engine)
+            .unwrap();
+        let restored = Checkpoint::load(&path, PrecisionPolicy::with_scale(2))
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(super::log_path(&path)).unwrap();
+
+        assert_eq!((restored.offset, restored.records), (42, 4));
+        assert!(restored.input_position.is_none());
+        assert_eq!(restored.engine.precision().scale, 2);
+        let display = PrecisionPolicy::default();
+        assert_eq!(
+            output_records(&restored.engine.client_manager, &display).collect::<Vec<_>>(),
+            output_records(&payment_engine.client_manager, &display).collect::<Vec<_>>()
+        );
+        assert!(
+            restored
+                .engine
+                .tx_manager
+                .get(1.into())
+                .unwrap()
+                .is_disputed()
+        );
+        restored
+            .engine
+            .journal
+            .reconcile(&restored.engine.client_manager)
+            .unwrap();
+        assert!(Checkpoint::load(&path, display).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_later_checkpoints_append_changes() {
+        let path = std::env::temp_dir().join(format!(
+            "silhouette-checkpoint-log-{}.json",
+            std::process::id()
+        ));
+        let records = |csv: &'static str| {
+            csv_stream(
+                format!("type,client,tx,amount\n{csv}")
+                    .into_bytes()
+                    .as_slice(),
+            )
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+        };
+        let mut payment_engine = PaymentsEngine::default();
+        payment_engine.keep_history();
+        let mut writer = CheckpointWriter::new(&path);
+        for record in records("deposit,1,1,10\ndeposit,2,2,3\n") {
+            payment_engine.process_csv_record(record).unwrap();
+        }
+        writer.save(0, 2, None, &mut payment_engine).unwrap();
+        let base = std::fs::read(&path).unwrap();
+
+        for record in records("dispute,1,1,\nwithdrawal,2,3,1\n") {
+            payment_engine.process_csv_record(record).unwrap();
+        }
+        writer.save(0, 4, None, &mut payment_engine).unwrap();
+        payment_engine
+            .process_batch(records("deposit,3,4,1\nwithdrawal,2,5,9\n"))
+            .unwrap_err();
+        for record in records("chargeback,1,1,\n") {
+            payment_engine.process_csv_record(record).unwrap();
+        }
+        writer.save(0, 7, None, &mut payment_engine).unwrap();
+        // Only the base holds everything; the changes after it are appended beside it
+        assert_eq!(std::fs::read(&path).unwrap(), base);
+
+        // A change torn by a crash mid-append is dropped with everything after it
+        let log = super::log_path(&path);
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+        std::io::Write::write_all(&mut file, br#"{"log":"#).unwrap();
+
+        let display = PrecisionPolicy::default();
+        let restored = Checkpoint::load(&path, display).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&log).unwrap();
+
+        assert_eq!(restored.records, 7);
+        assert_eq!(restored.engine.position(), payment_engine.position());
+        assert_eq!(
+            output_records(&restored.engine.client_manager, &display).collect::<Vec<_>>(),
+            output_records(&payment_engine.client_manager, &display).collect::<Vec<_>>()
+        );
+        assert!(!restored.engine.tx_manager.exists(4.into()));
+        assert_eq!(
+            restored.engine.journal.entries(),
+            payment_engine.journal.entries()
+        );
+        let history = restored.engine.history().unwrap();
+        assert_eq!(
+            history.accounts_at(3, &restored.engine.journal),
+            (payment_engine.history().unwrap()).accounts_at(3, &payment_engine.journal)
+        );
+    }
+}
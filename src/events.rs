@@ -0,0 +1,217 @@
+//! Typed events the engine emits to subscribed observers.
+
+use std::sync::mpsc::Sender;
+
+use bigdecimal::{BigDecimal, num_traits::zero};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ledger::client_manager::ClientAccount,
+    model::{ClientId, TxId, TxType},
+    output::{deserialise_decimal, serialise_decimal},
+    transaction::TransactionError,
+};
+
+/// A client's balances at one point in time. An account that does not exist yet is all zero.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Balances {
+    #[serde(
+        serialize_with = "serialise_decimal",
+        deserialize_with = "deserialise_decimal"
+    )]
+    pub available: BigDecimal,
+    #[serde(
+        serialize_with = "serialise_decimal",
+        deserialize_with = "deserialise_decimal"
+    )]
+    pub held: BigDecimal,
+    #[serde(
+        serialize_with = "serialise_decimal",
+        deserialize_with = "deserialise_decimal"
+    )]
+    pub total: BigDecimal,
+    pub locked: bool,
+}
+
+impl Default for Balances {
+    fn default() -> Self {
+        Self {
+            available: zero(),
+            held: zero(),
+            total: zero(),
+            locked: false,
+        }
+    }
+}
+
+impl From<&ClientAccount> for Balances {
+    fn from(account: &ClientAccount) -> Self {
+        Self {
+            available: account.available.clone(),
+            held: account.held.clone(),
+            total: account.total(),
+            locked: account.is_locked(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    Deposited {
+        #[serde(serialize_with = "serialise_decimal")]
+        amount: BigDecimal,
+    },
+    Withdrawn {
+        #[serde(serialize_with = "serialise_decimal")]
+        amount: BigDecimal,
+    },
+    DisputeOpened {
+        #[serde(serialize_with = "serialise_decimal")]
+        amount: BigDecimal,
+    },
+    DisputeResolved {
+        #[serde(serialize_with = "serialise_decimal")]
+        amount: BigDecimal,
+    },
+    ChargedBack {
+        #[serde(serialize_with = "serialise_decimal")]
+        amount: BigDecimal,
+    },
+    /// Follows the [`ChargedBack`](EventKind::ChargedBack) that locked the account.
+    AccountLocked,
+    /// An accepted record that changed nothing, such as the dispute of a withdrawal, so
+    /// `before` and `after` are equal.
+    Ignored { operation: TxType },
+    /// Nothing changed, so `before` and `after` are equal.
+    Rejected { error: TransactionError },
+}
+
+/// One change to a client account, or one accepted or rejected record that changed nothing, in
+/// the order the engine applied them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EngineEvent {
+    /// The engine [`position`](crate::ledger::engine::PaymentsEngine::position) of the record.
+    pub position: u64,
+    pub client: ClientId,
+    pub tx: TxId,
+    #[serde(flatten)]
+    pub kind: EventKind,
+    pub before: Balances,
+    pub after: Balances,
+}
+
+/// Receives every event from the engines it is subscribed to.
+pub trait EngineObserver {
+    fn on_event(&mut self, event: &EngineEvent);
+}
+
+impl<F: FnMut(&EngineEvent)> EngineObserver for F {
+    fn on_event(&mut self, event: &EngineEvent) {
+        self(event)
+    }
+}
+
+/// Forwards events to another thread. Once the receiver is dropped, events are discarded.
+impl EngineObserver for Sender<EngineEvent> {
+    fn on_event(&mut self, event: &EngineEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use crate::{events::EventKind, file_reader::csv_stream, ledger::engine::PaymentsEngine};
+
+    #[test]
+    fn test_events_carry_before_and_after_balances() {
+        let test_data = r#"type,client,tx,amount
+deposit,1,1,10.0
+withdrawal,1,2,4.0
+dispute,1,2,
+dispute,1,1,
+resolve,1,1,
+dispute,1,1,
+chargeback,1,1,
+deposit,1,3,1.0
+"#;
+        let (sender, receiver) = mpsc::channel();
+        let mut payment_engine = PaymentsEngine::default();
+        payment_engine.subscribe(sender);
+        for record in csv_stream(test_data.as_bytes()) {
+            let _ = payment_engine.process_csv_record(record.unwrap());
+        }
+
+        let events = receiver.try_iter().collect::<Vec<_>>();
+        let kinds = events
+            .iter()
+            .map(|event| match &event.kind {
+                EventKind::Rejected { .. } => "rejected",
+                EventKind::Deposited { .. } => "deposited",
+                EventKind::Withdrawn { .. } => "withdrawn",
+                EventKind::DisputeOpened { .. } => "dispute_opened",
+                EventKind::DisputeResolved { .. } => "dispute_resolved",
+                EventKind::ChargedBack { .. } => "charged_back",
+                EventKind::AccountLocked => "account_locked",
+                EventKind::Ignored { .. } => "ignored",
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                "deposited",
+                "withdrawn",
+                "ignored",
+                "dispute_opened",
+                "dispute_resolved",
+                "dispute_opened",
+                "charged_back",
+                "account_locked",
+                "rejected",
+            ]
+        );
+
+        // The dispute of a withdrawal is accepted but changes nothing
+        assert_eq!(events[2].position, 3);
+        assert_eq!(events[2].before, events[2].after);
+        let dispute = &events[3];
+        assert_eq!(dispute.position, 4);
+        assert_eq!(dispute.before.available, "6".parse().unwrap());
+        assert_eq!(dispute.after.available, "-4".parse().unwrap());
+        assert_eq!(dispute.after.held, "10".parse().unwrap());
+        assert!(!events[6].before.locked && events[6].after.locked);
+        assert_eq!(events[6].position, events[7].position);
+        assert_eq!(events[8].before, events[8].after);
+        assert!(matches!(
+            &events[8].kind,
+            EventKind::Rejected { error } if error.code() == "account_locked"
+        ));
+
+        let json = serde_json::to_value(&events[0]).unwrap();
+        assert_eq!(json["event"], "deposited");
+        assert_eq!(json["amount"], "10.0000");
+        assert_eq!(json["after"]["total"], "10.0000");
+    }
+
+    #[test]
+    fn test_batch_events_wait_for_commit() {
+        let (sender, receiver) = mpsc::channel();
+        let mut payment_engine = PaymentsEngine::default();
+        payment_engine.subscribe(sender);
+
+        let batch = "type,client,tx,amount\ndeposit,1,1,5.0\nwithdrawal,1,2,9.0\n";
+        let records = csv_stream(batch.as_bytes()).map(Result::unwrap);
+        payment_engine.process_batch(records).unwrap_err();
+        let events = receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].kind, EventKind::Rejected { .. }));
+        assert_eq!(events[0].after.total, "0".parse().unwrap());
+
+        let batch = batch.replace("9.0", "2.0");
+        let records = csv_stream(batch.as_bytes()).map(Result::unwrap);
+        payment_engine.process_batch(records).unwrap();
+        assert_eq!(receiver.try_iter().count(), 2);
+    }
+}
@@ -1,6 +1,122 @@
-use std::io;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    str::FromStr,
+};
 
-use crate::model::CSVRecord;
+use flate2::bufread::MultiGzDecoder;
+use serde::{Deserialize, Deserializer, de::Error};
+use serde_json::value::RawValue;
+
+use crate::{
+    model::{CSVRecord, ClientId, RawRecord, TxId, TxType},
+    schema::{Column, CsvSchema},
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "gz" | "gzip" => Some(Compression::Gzip),
+            "zst" | "zstd" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    pub fn from_magic(bytes: &[u8]) -> Self {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wraps `reader` in the decompressor matching its leading magic bytes.
+pub fn decompress<'a, R: BufRead + 'a>(reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    decompress_as(reader, None)
+}
+
+fn decompress_as<'a, R: BufRead + 'a>(
+    mut reader: R,
+    compression: Option<Compression>,
+) -> io::Result<Box<dyn BufRead + 'a>> {
+    let compression = match compression {
+        Some(compression) => compression,
+        None => Compression::from_magic(reader.fill_buf()?),
+    };
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(
+            reader,
+        )?)),
+    })
+}
+
+/// Opens `path`, decompressing by file extension (`.gz`, `.zst`) or, failing that, magic bytes.
+pub fn open_path(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let reader = BufReader::new(File::open(path)?);
+    decompress_as(reader, Compression::from_extension(path))
+}
+
+/// Whether [`open_path`] would decompress `path`. Only uncompressed files can be reopened part
+/// way through with [`open_path_at`].
+pub fn is_compressed(path: &Path) -> io::Result<bool> {
+    if Compression::from_extension(path).is_some() {
+        return Ok(true);
+    }
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(Compression::from_magic(reader.fill_buf()?) != Compression::None)
+}
+
+/// Opens the uncompressed `path` at `offset`, as reported by [`positioned_record_stream`]. A CSV
+/// header is read from the start of the file and replayed first, so the records parse as they
+/// did from the top. Returns the reader and the file offset its own positions are relative to.
+pub fn open_path_at(
+    path: &Path,
+    offset: u64,
+    format: Option<InputFormat>,
+    schema: Option<&CsvSchema>,
+) -> io::Result<(Box<dyn BufRead>, u64)> {
+    let mut file = BufReader::new(File::open(path)?);
+    let format = match format {
+        Some(format) => format,
+        None => InputFormat::detect(&mut file)?,
+    };
+
+    let has_headers = format == InputFormat::Csv && schema.is_none_or(|schema| schema.has_headers);
+    let mut header = Vec::new();
+    if has_headers {
+        let delimiter = schema.map_or(b',', |schema| schema.delimiter as u8);
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(delimiter)
+            .from_reader(&mut file);
+        reader.read_byte_record(&mut csv::ByteRecord::new())?;
+        let len = reader.position().byte();
+        drop(reader);
+        file.seek(SeekFrom::Start(0))?;
+        (&mut file).take(len).read_to_end(&mut header)?;
+    }
+
+    file.seek(SeekFrom::Start(offset))?;
+    let base = offset.saturating_sub(header.len() as u64);
+    Ok((Box::new(io::Cursor::new(header).chain(file)), base))
+}
 
 pub fn csv_stream<R: io::Read>(buffer: R) -> impl Iterator<Item = Result<CSVRecord, csv::Error>> {
     let reader = csv::ReaderBuilder::new()
@@ -11,8 +127,343 @@ pub fn csv_stream<R: io::Read>(buffer: R) -> impl Iterator<Item = Result<CSVReco
     reader.into_deserialize::<CSVRecord>()
 }
 
+/// [`csv_stream`] without parsing the amount.
+pub fn raw_csv_stream<R: io::Read>(
+    buffer: R,
+) -> impl Iterator<Item = Result<RawRecord, csv::Error>> {
+    let reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(buffer);
+
+    reader.into_deserialize::<RawRecord>()
+}
+
+/// Parses one headerless `type,client,tx,amount` line, as sent by streaming producers, or `None`
+/// for a blank line. A dispute, resolve or chargeback may omit the trailing amount column.
+pub fn parse_csv_line(line: &str) -> Result<Option<RawRecord>, ReadError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(line.as_bytes());
+
+    let mut record = csv::StringRecord::new();
+    if !reader.read_record(&mut record)? {
+        return Ok(None);
+    }
+    let canonical = csv::StringRecord::from(vec!["type", "client", "tx", "amount"]);
+    Ok(Some(record.deserialize::<RawRecord>(Some(&canonical))?))
+}
+
+fn parse_raw(record: RawRecord) -> Result<CSVRecord, ReadError> {
+    CSVRecord::try_from(record).map_err(ReadError::Amount)
+}
+
+fn resolve_column(
+    column: &Column,
+    headers: Option<&csv::StringRecord>,
+) -> Result<usize, ReadError> {
+    match (column, headers) {
+        (Column::Index(idx), _) => Ok(*idx),
+        (Column::Name(name), Some(headers)) => headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| ReadError::Schema(format!("no column named `{name}`"))),
+        (Column::Name(name), None) => Err(ReadError::Schema(format!(
+            "column `{name}` is mapped by name but the schema has no headers"
+        ))),
+    }
+}
+
+/// Like [`csv_stream`], but the columns, header presence and delimiter come from `schema`.
+/// Rows may carry extra columns, and a missing trailing amount is treated as empty.
+pub fn csv_stream_with_schema<R: io::Read>(
+    buffer: R,
+    schema: &CsvSchema,
+) -> Result<impl Iterator<Item = Result<CSVRecord, ReadError>> + use<R>, ReadError> {
+    Ok(raw_csv_stream_with_schema(buffer, schema)?.map(|record| parse_raw(record?)))
+}
+
+/// [`csv_stream_with_schema`] without parsing the amount.
+pub fn raw_csv_stream_with_schema<R: io::Read>(
+    buffer: R,
+    schema: &CsvSchema,
+) -> Result<impl Iterator<Item = Result<RawRecord, ReadError>> + use<R>, ReadError> {
+    Ok(positioned_csv_stream_with_schema(buffer, schema)?.map(|(_, record)| record))
+}
+
+/// Reads CSV rows with the byte offset just past each, converted by `parse`.
+fn positioned_rows<R: io::Read>(
+    mut reader: csv::Reader<R>,
+    mut parse: impl FnMut(&mut csv::Reader<R>, &csv::StringRecord) -> Result<RawRecord, ReadError>,
+) -> impl Iterator<Item = PositionedRecord> {
+    let mut record = csv::StringRecord::new();
+    std::iter::from_fn(move || {
+        let row = match reader.read_record(&mut record) {
+            Ok(false) => return None,
+            Ok(true) => parse(&mut reader, &record),
+            Err(err) => Err(err.into()),
+        };
+        Some((reader.position().byte(), row))
+    })
+}
+
+fn positioned_csv_stream<R: io::Read>(buffer: R) -> impl Iterator<Item = PositionedRecord> {
+    let reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .trim(csv::Trim::All)
+        .from_reader(buffer);
+
+    positioned_rows(reader, |reader, record| {
+        let headers = reader.headers()?;
+        Ok(record.deserialize::<RawRecord>(Some(headers))?)
+    })
+}
+
+fn positioned_csv_stream_with_schema<R: io::Read>(
+    buffer: R,
+    schema: &CsvSchema,
+) -> Result<impl Iterator<Item = PositionedRecord> + use<R>, ReadError> {
+    if !schema.delimiter.is_ascii() {
+        return Err(ReadError::Schema(format!(
+            "delimiter {:?} is not a single-byte character",
+            schema.delimiter
+        )));
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(schema.has_headers)
+        .delimiter(schema.delimiter as u8)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(buffer);
+
+    let headers = match schema.has_headers {
+        true => Some(reader.headers()?.clone()),
+        false => None,
+    };
+    let mapping = schema.mapping();
+    let mut indices = [0; 4];
+    for (idx, column) in indices.iter_mut().zip(mapping.columns()) {
+        *idx = resolve_column(column, headers.as_ref())?;
+    }
+    let batch = match &mapping.batch {
+        Some(column) => Some(resolve_column(column, headers.as_ref())?),
+        None => None,
+    };
+
+    let canonical = csv::StringRecord::from(vec!["type", "client", "tx", "amount", "batch"]);
+    Ok(positioned_rows(reader, move |_, record| {
+        let mapped = indices
+            .iter()
+            .map(|idx| record.get(*idx).unwrap_or_default())
+            .chain(batch.map(|idx| record.get(idx).unwrap_or_default()))
+            .collect::<csv::StringRecord>();
+        Ok(mapped.deserialize::<RawRecord>(Some(&canonical))?)
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Csv,
+    JsonLines,
+}
+
+impl FromStr for InputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(InputFormat::Csv),
+            "jsonl" => Ok(InputFormat::JsonLines),
+            _ => Err(format!("unknown input format `{s}`, expected csv or jsonl")),
+        }
+    }
+}
+
+impl InputFormat {
+    /// Recognises `.jsonl` and `.ndjson`, including beneath a compression extension.
+    pub fn from_extension(path: &Path) -> Option<Self> {
+        let path = match Compression::from_extension(path) {
+            Some(_) => Path::new(path.file_stem()?),
+            None => path,
+        };
+        match path.extension()?.to_str()? {
+            "jsonl" | "ndjson" => Some(InputFormat::JsonLines),
+            "csv" => Some(InputFormat::Csv),
+            _ => None,
+        }
+    }
+
+    /// A JSON Lines input starts with an object, a CSV input with its header.
+    pub fn detect<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        let first = reader
+            .fill_buf()?
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace());
+        Ok(match first {
+            Some(b'{') => InputFormat::JsonLines,
+            _ => InputFormat::Csv,
+        })
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadError {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error("line {line}: {source}")]
+    Json {
+        line: usize,
+        source: serde_json::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("schema: {0}")]
+    Schema(String),
+    #[error("{0}")]
+    Amount(String),
+}
+
+/// Accepts an amount or batch id as a JSON string or number, keeping its exact text so numbers
+/// are not routed through `f64`.
+fn deserialize_json_text<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(raw) = Option::<Box<RawValue>>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let raw = raw.get();
+    if raw.starts_with('"') {
+        serde_json::from_str::<String>(raw)
+            .map(Some)
+            .map_err(Error::custom)
+    } else {
+        Ok(Some(raw.to_owned()))
+    }
+}
+
+/// A record in its JSON form, as used by JSON Lines input and the HTTP API.
+#[derive(Deserialize)]
+pub struct JsonRecord {
+    r#type: TxType,
+    client: ClientId,
+    tx: TxId,
+    #[serde(default, deserialize_with = "deserialize_json_text")]
+    amount: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_json_text")]
+    batch: Option<String>,
+}
+
+impl From<JsonRecord> for RawRecord {
+    fn from(value: JsonRecord) -> Self {
+        RawRecord {
+            r#type: value.r#type,
+            client: value.client,
+            tx: value.tx,
+            amount: value.amount,
+            batch: value.batch,
+        }
+    }
+}
+
+pub fn jsonl_stream<R: BufRead>(buffer: R) -> impl Iterator<Item = Result<CSVRecord, ReadError>> {
+    raw_jsonl_stream(buffer).map(|record| parse_raw(record?))
+}
+
+/// [`jsonl_stream`] without parsing the amount.
+pub fn raw_jsonl_stream<R: BufRead>(
+    buffer: R,
+) -> impl Iterator<Item = Result<RawRecord, ReadError>> {
+    positioned_jsonl_stream(buffer).map(|(_, record)| record)
+}
+
+fn positioned_jsonl_stream<R: BufRead>(mut buffer: R) -> impl Iterator<Item = PositionedRecord> {
+    let (mut offset, mut line_no) = (0, 0);
+    let mut line = Vec::new();
+    std::iter::from_fn(move || {
+        loop {
+            line.clear();
+            let read = match buffer.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Ok(read) => read,
+                Err(err) => return Some((offset, Err(err.into()))),
+            };
+            offset += read as u64;
+            line_no += 1;
+            let text = match std::str::from_utf8(&line) {
+                Ok(text) => text,
+                Err(err) => {
+                    let err = io::Error::new(io::ErrorKind::InvalidData, err);
+                    return Some((offset, Err(err.into())));
+                }
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str::<JsonRecord>(text)
+                .map(RawRecord::from)
+                .map_err(|source| ReadError::Json {
+                    line: line_no,
+                    source,
+                });
+            return Some((offset, record));
+        }
+    })
+}
+
+/// Streams records from `reader` in the given format, or the detected one when `None`. A CSV
+/// `schema` replaces the standard `type,client,tx,amount` layout.
+pub fn record_stream<'a, R: BufRead + 'a>(
+    reader: R,
+    format: Option<InputFormat>,
+    schema: Option<&CsvSchema>,
+) -> Result<Box<dyn Iterator<Item = Result<CSVRecord, ReadError>> + 'a>, ReadError> {
+    Ok(Box::new(
+        raw_record_stream(reader, format, schema)?.map(|record| parse_raw(record?)),
+    ))
+}
+
+/// [`record_stream`] without parsing the amount, for strict validation.
+pub fn raw_record_stream<'a, R: BufRead + 'a>(
+    reader: R,
+    format: Option<InputFormat>,
+    schema: Option<&CsvSchema>,
+) -> Result<Box<dyn Iterator<Item = Result<RawRecord, ReadError>> + 'a>, ReadError> {
+    Ok(Box::new(
+        positioned_record_stream(reader, format, schema)?.map(|(_, record)| record),
+    ))
+}
+
+/// A raw record with the byte offset just past its row.
+pub type PositionedRecord = (u64, Result<RawRecord, ReadError>);
+
+/// [`raw_record_stream`] with the byte offset just past each row, from which
+/// [`open_path_at`] can later pick up.
+pub fn positioned_record_stream<'a, R: BufRead + 'a>(
+    mut reader: R,
+    format: Option<InputFormat>,
+    schema: Option<&CsvSchema>,
+) -> Result<Box<dyn Iterator<Item = PositionedRecord> + 'a>, ReadError> {
+    let format = match format {
+        Some(format) => format,
+        None => InputFormat::detect(&mut reader)?,
+    };
+    Ok(match (format, schema) {
+        (InputFormat::Csv, None) => Box::new(positioned_csv_stream(reader)),
+        (InputFormat::Csv, Some(schema)) => {
+            Box::new(positioned_csv_stream_with_schema(reader, schema)?)
+        }
+        (InputFormat::JsonLines, _) => Box::new(positioned_jsonl_stream(reader)),
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use std::io::Write as _;
+
     use crate::model::{ClientId, TxId, TxType};
 
     #[test]
@@ -29,4 +480,204 @@ deposit,  1,  1,  100.0
         assert_eq!(record.tx, TxId::from(1));
         assert!(record.amount.is_some());
     }
+
+    #[test]
+    fn decompress_by_magic_bytes() {
+        let test_data = b"type,client,tx,amount\ndeposit,1,1,100.0\ndeposit,2,2,5.0\n";
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        gzip.write_all(test_data).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let zstd = zstd::encode_all(&test_data[..], 0).unwrap();
+
+        for input in [&test_data[..], &gzip, &zstd] {
+            let reader = super::decompress(input).unwrap();
+            let records = super::csv_stream(reader)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[1].client, ClientId::from(2));
+        }
+    }
+
+    #[test]
+    fn jsonl_amounts_match_csv_semantics() {
+        let test_data = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "100.00005"}
+
+{"type": "deposit", "client": 1, "tx": 2, "amount": 12345678901234.56789}
+{"type": "dispute", "client": 1, "tx": 1}
+{"type": "resolve", "client": 1, "tx": 1, "amount": null}
+{"type": "withdrawal", "client": 1, "tx": 3, "amount": " "}
+"#;
+
+        let records = super::jsonl_stream(test_data.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[0].amount, Some("100.00005".parse().unwrap()));
+        assert_eq!(
+            records[1].amount,
+            Some("12345678901234.56789".parse().unwrap())
+        );
+        assert_eq!(records[2].r#type, TxType::Dispute);
+        assert!(records[3].amount.is_none());
+        assert!(records[4].amount.is_none());
+
+        let mut reader = super::jsonl_stream(r#"{"type": "deposit"}"#.as_bytes());
+        assert!(matches!(
+            reader.next(),
+            Some(Err(super::ReadError::Json { line: 1, .. }))
+        ));
+    }
+
+    #[test]
+    fn detect_input_format() {
+        use super::InputFormat;
+
+        let mut jsonl = &b"\n {\"type\": \"deposit\"}"[..];
+        let mut csv = &b"type,client,tx,amount"[..];
+        assert_eq!(
+            InputFormat::detect(&mut jsonl).unwrap(),
+            InputFormat::JsonLines
+        );
+        assert_eq!(InputFormat::detect(&mut csv).unwrap(), InputFormat::Csv);
+
+        let path = std::path::Path::new("extract.ndjson.zst");
+        assert_eq!(
+            InputFormat::from_extension(path),
+            Some(InputFormat::JsonLines)
+        );
+    }
+
+    #[test]
+    fn schema_maps_renamed_and_reordered_columns() {
+        use crate::schema::CsvSchema;
+
+        let schema = serde_json::from_str::<CsvSchema>(
+            r#"{"delimiter": ";", "columns": {"type": "Kind", "client": "Customer", "tx": "Ref", "amount": "Value"}}"#,
+        )
+        .unwrap();
+        let test_data = "Ref; Note; Customer; Kind; Value
+1; first; 7; deposit; 100.00005
+2; second; 7; dispute
+";
+
+        let records = super::csv_stream_with_schema(test_data.as_bytes(), &schema)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].r#type, TxType::Deposit);
+        assert_eq!(records[0].client, ClientId::from(7));
+        assert_eq!(records[0].amount, Some("100.00005".parse().unwrap()));
+        assert_eq!(records[1].tx, TxId::from(2));
+        assert!(records[1].amount.is_none());
+    }
+
+    #[test]
+    fn schema_without_headers() {
+        use crate::schema::CsvSchema;
+
+        let schema = CsvSchema {
+            has_headers: false,
+            ..CsvSchema::default()
+        };
+        let test_data = "deposit,1,1,5.0\nwithdrawal,1,2,1.0\n";
+
+        let records = super::csv_stream_with_schema(test_data.as_bytes(), &schema)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].r#type, TxType::Withdrawal);
+
+        let schema = serde_json::from_str::<CsvSchema>(
+            r#"{"has_headers": false, "columns": {"type": "type", "client": 1, "tx": 2, "amount": 3}}"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            super::csv_stream_with_schema(test_data.as_bytes(), &schema),
+            Err(super::ReadError::Schema(_))
+        ));
+    }
+
+    #[test]
+    fn resume_part_way_through_a_file() {
+        use crate::schema::CsvSchema;
+
+        let csv = "type,client,tx,amount\ndeposit,1,1,1.0\n\ndeposit,1,2,2.0\ndeposit,1,3,3.0";
+        let jsonl = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1.0"}
+
+{"type": "deposit", "client": 1, "tx": 2, "amount": "2.0"}
+{"type": "deposit", "client": 1, "tx": 3, "amount": "3.0"}
+"#;
+        let semicolons = CsvSchema {
+            delimiter: ';',
+            ..CsvSchema::default()
+        };
+        let headerless = CsvSchema {
+            has_headers: false,
+            ..CsvSchema::default()
+        };
+        let semicolon_csv = csv.replace(',', ";");
+        let headerless_csv = csv.split_once('\n').unwrap().1;
+        let cases = [
+            (csv, None),
+            (jsonl, None),
+            (semicolon_csv.as_str(), Some(&semicolons)),
+            (headerless_csv, Some(&headerless)),
+        ];
+
+        let path = std::env::temp_dir().join(format!("silhouette-resume-{}", std::process::id()));
+        for (data, schema) in cases {
+            std::fs::write(&path, data).unwrap();
+            let read = |reader, base| {
+                super::positioned_record_stream(reader, None, schema)
+                    .unwrap()
+                    .map(|(end, record)| (base + end, record.unwrap().tx))
+                    .collect::<Vec<_>>()
+            };
+            let all = read(super::open_path(&path).unwrap(), 0);
+            assert_eq!(all.len(), 3);
+            assert_eq!(all[2].0, data.len() as u64);
+
+            let (reader, base) = super::open_path_at(&path, all[0].0, None, schema).unwrap();
+            assert_eq!(read(reader, base), all[1..]);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn batch_column_in_every_format() {
+        use crate::schema::CsvSchema;
+
+        let batches = |records: Vec<crate::model::RawRecord>| {
+            records.into_iter().map(|r| r.batch).collect::<Vec<_>>()
+        };
+        let some = |id: &str| Some(id.to_owned());
+
+        let csv = "type,client,tx,amount,batch\ndeposit,1,1,1.0,a\ndeposit,1,2,1.0,\n";
+        let records = super::raw_csv_stream(csv.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches(records), [some("a"), None]);
+
+        let jsonl = r#"{"type": "deposit", "client": 1, "tx": 1, "amount": "1", "batch": 7}
+{"type": "deposit", "client": 1, "tx": 2, "amount": "1", "batch": "b"}
+"#;
+        let records = super::raw_jsonl_stream(jsonl.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches(records), [some("7"), some("b")]);
+
+        let schema = serde_json::from_str::<CsvSchema>(
+            r#"{"has_headers": false, "columns": {"type": 1, "client": 2, "tx": 3, "amount": 4, "batch": 0}}"#,
+        )
+        .unwrap();
+        let records = super::raw_csv_stream_with_schema("x,deposit,1,1,1.0".as_bytes(), &schema)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches(records), [some("x")]);
+    }
 }
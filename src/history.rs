@@ -0,0 +1,280 @@
+//! Each client's balances over time, keyed by input position, for `--as-of` queries.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::Balances,
+    ledger::{
+        client_manager::ClientAccountManager,
+        journal::{Journal, LedgerAccount},
+    },
+    model::ClientId,
+};
+
+/// A snapshot is taken once the records since the last one number at least this many times the
+/// accounts they changed. Snapshots then hold at most one balance per this many records, and a
+/// query replays at most this many journal entries per account.
+const SNAPSHOT_RATIO: u64 = 32;
+
+/// Past balances, rebuilt from the nearest snapshot before a position plus the journal entries
+/// after it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BalanceHistory {
+    /// The position the history was started at. Earlier balances are unknown, except as the
+    /// starting balances recorded against it.
+    start: u64,
+    /// Keyed by the position each was taken after. Each holds the accounts changed since the one
+    /// before; the first holds every account at `start`.
+    snapshots: BTreeMap<u64, BTreeMap<ClientId, Balances>>,
+    /// The position each account was opened at, which a rejected record can do without posting.
+    opened: BTreeMap<ClientId, u64>,
+    /// Accounts changed since the last snapshot.
+    changed: BTreeSet<ClientId>,
+}
+
+/// What a [`BalanceHistory`] recorded from a position on; see [`BalanceHistory::since`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryChanges {
+    start: u64,
+    snapshots: BTreeMap<u64, BTreeMap<ClientId, Balances>>,
+    opened: BTreeMap<ClientId, u64>,
+    changed: BTreeSet<ClientId>,
+}
+
+impl BalanceHistory {
+    /// Starts a history at `position`, from the balances at that point.
+    pub(crate) fn new(position: u64, balances: impl Iterator<Item = (ClientId, Balances)>) -> Self {
+        let balances = balances.collect::<BTreeMap<_, _>>();
+        Self {
+            start: position,
+            opened: balances.keys().map(|client| (*client, position)).collect(),
+            snapshots: BTreeMap::from([(position, balances)]),
+            changed: BTreeSet::new(),
+        }
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// Notes that the record at `position` changed or opened `client`'s account.
+    pub(crate) fn record(&mut self, client: ClientId, position: u64) {
+        self.opened.entry(client).or_insert(position);
+        self.changed.insert(client);
+    }
+
+    /// Snapshots the changed accounts after the record at `position`, if one is due.
+    pub(crate) fn snapshot_if_due(&mut self, position: u64, clients: &ClientAccountManager) {
+        let last = self
+            .snapshots
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or(self.start);
+        let changed = self.changed.len() as u64;
+        if changed == 0 || position - last < SNAPSHOT_RATIO * changed {
+            return;
+        }
+        let snapshot = std::mem::take(&mut self.changed)
+            .into_iter()
+            .filter_map(|client| Some((client, clients.accounts.get(&client)?.into())))
+            .collect();
+        self.snapshots.insert(position, snapshot);
+    }
+
+    /// Forgets everything from `position` on, for a rolled-back batch.
+    pub(crate) fn rewind(&mut self, position: u64) {
+        for (_, snapshot) in self.snapshots.split_off(&position.max(self.start + 1)) {
+            self.changed.extend(snapshot.into_keys());
+        }
+        self.opened.retain(|_, opened| *opened < position);
+        self.changed
+            .retain(|client| self.opened.contains_key(client));
+    }
+
+    /// The snapshots and openings recorded from `position` on, which is everything if the
+    /// history was started since.
+    pub(crate) fn since(&self, position: u64) -> HistoryChanges {
+        HistoryChanges {
+            start: self.start,
+            snapshots: self
+                .snapshots
+                .range(position..)
+                .map(|(at, snapshot)| (*at, snapshot.clone()))
+                .collect(),
+            opened: self
+                .opened
+                .iter()
+                .filter(|(_, opened)| **opened >= position)
+                .map(|(client, opened)| (*client, *opened))
+                .collect(),
+            changed: self.changed.clone(),
+        }
+    }
+
+    /// Adds `changes` taken from a later copy of this history.
+    pub(crate) fn apply(&mut self, changes: HistoryChanges) {
+        self.start = changes.start;
+        self.snapshots.extend(changes.snapshots);
+        self.opened.extend(changes.opened);
+        self.changed = changes.changed;
+    }
+
+    /// `client`'s balances after the record at `position`, or `None` if it had no account yet.
+    pub fn balance_at(
+        &self,
+        client: ClientId,
+        position: u64,
+        journal: &Journal,
+    ) -> Option<Balances> {
+        self.accounts_at(position, journal).remove(&client)
+    }
+
+    /// Every account that existed after the record at `position`, in client order, or none before
+    /// [`start`](Self::start). `journal` is the engine's.
+    pub fn accounts_at(&self, position: u64, journal: &Journal) -> BTreeMap<ClientId, Balances> {
+        let mut accounts = BTreeMap::new();
+        if position < self.start {
+            return accounts;
+        }
+        for snapshot in self
+            .snapshots
+            .range(..=position)
+            .map(|(_, snapshot)| snapshot)
+        {
+            accounts.extend(
+                snapshot
+                    .iter()
+                    .map(|(client, balances)| (*client, balances.clone())),
+            );
+        }
+        let base = self
+            .snapshots
+            .range(..=position)
+            .next_back()
+            .map_or(self.start, |(at, _)| *at);
+
+        let entries = journal.entries();
+        let from = entries.partition_point(|entry| entry.position <= base);
+        let to = entries.partition_point(|entry| entry.position <= position);
+        for entry in &entries[from..to] {
+            let mut post = |account, amount: &bigdecimal::BigDecimal| {
+                let (client, held) = match account {
+                    LedgerAccount::ClientAvailable(client) => (client, false),
+                    LedgerAccount::ClientHeld(client) => (client, true),
+                    LedgerAccount::Settlement | LedgerAccount::ChargebackLoss => return,
+                };
+                let balances: &mut Balances = accounts.entry(client).or_default();
+                match held {
+                    true => balances.held += amount,
+                    false => balances.available += amount,
+                }
+                balances.total += amount;
+            };
+            post(entry.debit, &-&entry.amount);
+            post(entry.credit, &entry.amount);
+            // A chargeback is the only way an account is locked
+            if let (LedgerAccount::ClientHeld(client), LedgerAccount::ChargebackLoss) =
+                (entry.debit, entry.credit)
+            {
+                accounts.entry(client).or_default().locked = true;
+            }
+        }
+
+        for (client, _) in self.opened.iter().filter(|(_, at)| **at <= position) {
+            accounts.entry(*client).or_default();
+        }
+        accounts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{events::Balances, file_reader::csv_stream, ledger::engine::PaymentsEngine};
+
+    #[test]
+    fn test_balances_after_any_record() {
+        let test_data = r#"type,client,tx,amount
+deposit,1,1,10.0
+deposit,2,2,5.0
+withdrawal,1,3,4.0
+dispute,1,1,
+withdrawal,2,4,50.0
+chargeback,1,1,
+"#;
+        let mut payment_engine = PaymentsEngine::default();
+        let mut records = csv_stream(test_data.as_bytes()).map(Result::unwrap);
+        payment_engine
+            .process_csv_record(records.next().unwrap())
+            .unwrap();
+        payment_engine.keep_history();
+        for record in records {
+            let _ = payment_engine.process_csv_record(record);
+        }
+
+        let history = payment_engine.history().unwrap();
+        let journal = &payment_engine.journal;
+        assert_eq!(history.start(), 1);
+        let available = |client: u16, position| {
+            history
+                .balance_at(client.into(), position, journal)
+                .map(|balances| balances.available.to_string())
+        };
+        assert_eq!(available(1, 0), None);
+        assert_eq!(available(1, 1).as_deref(), Some("10.0000"));
+        assert_eq!(available(2, 1), None);
+        assert_eq!(available(1, 3).as_deref(), Some("6.0000"));
+        assert_eq!(available(1, 4).as_deref(), Some("-4.0000"));
+        assert_eq!(available(2, 5).as_deref(), Some("5.0000"));
+        assert!(history.balance_at(1.into(), 6, journal).unwrap().locked);
+        assert_eq!(history.accounts_at(2, journal).len(), 2);
+
+        // A rolled-back batch leaves no trace, but still advances the position
+        let batch = "type,client,tx,amount\ndeposit,3,5,1.0\nwithdrawal,2,6,9.0\n";
+        let records = csv_stream(batch.as_bytes()).map(Result::unwrap);
+        payment_engine.process_batch(records).unwrap_err();
+        let history = payment_engine.history().unwrap();
+        assert_eq!(payment_engine.position(), 8);
+        assert_eq!(history.accounts_at(8, &payment_engine.journal).len(), 2);
+    }
+
+    #[test]
+    fn test_snapshots_agree_with_live_balances() {
+        let mut payment_engine = PaymentsEngine::default();
+        payment_engine.keep_history();
+        let mut expected = Vec::new();
+        for tx in 1..=400u32 {
+            let client = tx % 3;
+            let record = match tx % 7 {
+                0 => format!("withdrawal,{client},{tx},2.5"),
+                5 => format!("dispute,{client},{},", tx - 3),
+                6 => format!("resolve,{client},{},", tx - 4),
+                _ => format!("deposit,{client},{tx},{tx}.25"),
+            };
+            let csv = format!("type,client,tx,amount\n{record}\n");
+            for record in csv_stream(csv.as_bytes()).map(Result::unwrap) {
+                let _ = payment_engine.process_csv_record(record);
+            }
+            let live = payment_engine
+                .client_manager
+                .accounts
+                .iter()
+                .map(|(client, account)| (*client, Balances::from(account)))
+                .collect::<Vec<_>>();
+            expected.push((payment_engine.position(), live));
+        }
+
+        let history = payment_engine.history().unwrap();
+        assert!(history.snapshots.len() > 2);
+        for (position, live) in expected {
+            let rebuilt = history.accounts_at(position, &payment_engine.journal);
+            assert_eq!(
+                rebuilt.into_iter().collect::<Vec<_>>(),
+                live,
+                "after record {position}"
+            );
+        }
+    }
+}
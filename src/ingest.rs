@@ -0,0 +1,162 @@
+//! Line-based record ingestion over TCP and Unix domain sockets.
+
+use std::{fmt, io, time::Duration};
+
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+
+use crate::{
+    file_reader::parse_csv_line,
+    server::{EngineHandle, Outcome},
+};
+
+/// The longest line accepted, in bytes. A record is far shorter; a longer line is answered with
+/// `error malformed` and discarded without being buffered.
+pub const MAX_LINE_LEN: usize = 4096;
+
+/// How long to wait after a failed accept, which is often a shortage of file descriptors.
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A failure outside any one record, handed to the caller's `on_error` to report.
+#[derive(Debug, thiserror::Error)]
+pub enum IngestError {
+    #[error("failed to accept a producer: {0}")]
+    Accept(io::Error),
+    #[error("{producer}: {source}")]
+    Producer { producer: String, source: io::Error },
+}
+
+enum Line {
+    Complete,
+    TooLong,
+}
+
+/// Reads the next line into `buf`, without its newline. Returns `None` at the end of the stream.
+async fn next_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> io::Result<Option<Line>> {
+    buf.clear();
+    let mut too_long = false;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(match (too_long, buf.is_empty()) {
+                (true, _) => Some(Line::TooLong),
+                (false, true) => None,
+                (false, false) => Some(Line::Complete),
+            });
+        }
+        let newline = available.iter().position(|b| *b == b'\n');
+        let line = &available[..newline.unwrap_or(available.len())];
+        if buf.len() + line.len() > MAX_LINE_LEN {
+            too_long = true;
+            buf.clear();
+        } else if !too_long {
+            buf.extend_from_slice(line);
+        }
+        let used = newline.map_or(available.len(), |i| i + 1);
+        reader.consume(used);
+        if newline.is_some() {
+            return Ok(Some(if too_long {
+                Line::TooLong
+            } else {
+                Line::Complete
+            }));
+        }
+    }
+}
+
+/// The reply line for one input line, or `None` for a blank line.
+async fn reply(handle: &EngineHandle, line: &str) -> io::Result<Option<String>> {
+    let record = match parse_csv_line(line) {
+        Ok(Some(record)) => record,
+        Ok(None) => return Ok(None),
+        Err(err) => return Ok(Some(format!("error malformed: {err}"))),
+    };
+
+    let outcome = handle
+        .apply(vec![record])
+        .await
+        .and_then(|mut outcomes| outcomes.pop())
+        .ok_or_else(|| io::Error::other("engine has stopped"))?;
+    Ok(Some(match outcome {
+        Outcome::Applied { tx } => format!("ok {tx}"),
+        Outcome::Rejected { tx, error } => format!("error {tx} {}: {error}", error.code()),
+    }))
+}
+
+/// Applies every line from `stream` until the producer closes it.
+pub async fn ingest<S: AsyncRead + AsyncWrite>(stream: S, handle: EngineHandle) -> io::Result<()> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+
+    while let Some(line) = next_line(&mut reader, &mut buf).await? {
+        let reply = match (line, std::str::from_utf8(&buf)) {
+            (Line::TooLong, _) => Some(format!(
+                "error malformed: line longer than {MAX_LINE_LEN} bytes"
+            )),
+            (Line::Complete, Err(err)) => Some(format!("error malformed: {err}")),
+            (Line::Complete, Ok(line)) if line.trim().is_empty() => None,
+            (Line::Complete, Ok(line)) => reply(&handle, line).await?,
+        };
+        if let Some(reply) = reply {
+            writer.write_all(reply.as_bytes()).await?;
+            writer.write_all(b"\n").await?;
+        }
+    }
+    writer.flush().await
+}
+
+/// Accepts producers on `listener` until the process exits, each on its own task. A failed
+/// accept or a broken connection is passed to `on_error` and ingestion carries on.
+pub async fn serve_tcp(
+    listener: TcpListener,
+    handle: EngineHandle,
+    on_error: impl Fn(IngestError) + Clone + Send + 'static,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => spawn(stream, peer, &handle, &on_error),
+            Err(err) => {
+                on_error(IngestError::Accept(err));
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+            }
+        }
+    }
+}
+
+/// [`serve_tcp`] for a Unix domain socket.
+#[cfg(unix)]
+pub async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    handle: EngineHandle,
+    on_error: impl Fn(IngestError) + Clone + Send + 'static,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => spawn(stream, "unix socket producer", &handle, &on_error),
+            Err(err) => {
+                on_error(IngestError::Accept(err));
+                tokio::time::sleep(ACCEPT_BACKOFF).await;
+            }
+        }
+    }
+}
+
+fn spawn<S: AsyncRead + AsyncWrite + Send + 'static>(
+    stream: S,
+    producer: impl fmt::Display,
+    handle: &EngineHandle,
+    on_error: &(impl Fn(IngestError) + Clone + Send + 'static),
+) {
+    let (producer, handle, on_error) = (producer.to_string(), handle.clone(), on_error.clone());
+    tokio::spawn(async move {
+        if let Err(source) = ingest(stream, handle).await {
+            on_error(IngestError::Producer { producer, source });
+        }
+    });
+}
@@ -3,18 +3,28 @@ pub mod client_manager {
 
     use bigdecimal::{BigDecimal, num_traits::zero};
 
+    use serde::{Deserialize, Serialize};
+
     use crate::model::ClientId;
 
-    #[derive(Default, Debug, PartialEq, Eq)]
+    #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
     pub enum ClientAccountStatus {
         #[default]
         Active,
         Locked,
     }
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Serialize, Deserialize)]
     pub struct ClientAccount {
+        #[serde(
+            serialize_with = "crate::output::serialise_decimal",
+            deserialize_with = "crate::output::deserialise_decimal"
+        )]
         pub available: BigDecimal,
+        #[serde(
+            serialize_with = "crate::output::serialise_decimal",
+            deserialize_with = "crate::output::deserialise_decimal"
+        )]
         pub held: BigDecimal,
         pub status: ClientAccountStatus,
     }
@@ -39,7 +49,8 @@ pub mod client_manager {
         }
     }
 
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
+    #[serde(transparent)]
     pub struct ClientAccountManager {
         pub(crate) accounts: BTreeMap<ClientId, ClientAccount>,
     }
@@ -50,6 +61,10 @@ pub mod client_manager {
             self.accounts.entry(client).or_default()
         }
 
+        pub fn exists(&self, client: ClientId) -> bool {
+            self.accounts.contains_key(&client)
+        }
+
         #[cfg(test)]
         pub fn client_count(&self) -> usize {
             self.accounts.len()
@@ -79,14 +94,17 @@ pub mod client_manager {
 mod tx_manager {
     use std::collections::{BTreeMap, btree_map::Entry};
 
+    use serde::{Deserialize, Serialize};
+
     use crate::{
-        model::TxId,
+        model::{ClientId, TxId},
         transaction::{Transaction, TransactionError, TransactionStatus, TxResult},
     };
 
-    #[derive(Default)]
+    #[derive(Default, Serialize, Deserialize)]
+    #[serde(transparent)]
     pub struct TxManager {
-        transactions: BTreeMap<TxId, Transaction>,
+        pub(crate) transactions: BTreeMap<TxId, Transaction>,
     }
 
     impl TxManager {
@@ -107,12 +125,21 @@ mod tx_manager {
             self.transactions.get(&tx)
         }
 
-        pub fn set_status(&mut self, tx: TxId, status: TransactionStatus) -> TxResult {
+        pub fn iter(&self) -> impl Iterator<Item = &Transaction> {
+            self.transactions.values()
+        }
+
+        pub fn set_status(
+            &mut self,
+            client: ClientId,
+            tx: TxId,
+            status: TransactionStatus,
+        ) -> TxResult {
             if let Entry::Occupied(mut e) = self.transactions.entry(tx) {
                 e.get_mut().status = status;
                 Ok(())
             } else {
-                Err(TransactionError::MissingTransaction(tx))
+                Err(TransactionError::MissingTransaction { client, tx })
             }
         }
 
@@ -153,7 +180,7 @@ mod tx_manager {
             manager.insert(valid_record);
 
             manager
-                .set_status(1.into(), TransactionStatus::Disputed)
+                .set_status(1.into(), 1.into(), TransactionStatus::Disputed)
                 .unwrap();
             assert!(manager.is_disputed(1.into()));
 
@@ -165,42 +192,406 @@ mod tx_manager {
             };
 
             let tx = Transaction::try_from(invalid_record);
-            assert!(matches!(tx, Err(TransactionError::MissingAmount)));
+            assert!(matches!(tx, Err(TransactionError::MissingAmount { .. })));
+        }
+    }
+}
+
+pub mod journal {
+    use std::collections::BTreeMap;
+
+    use bigdecimal::{BigDecimal, num_traits::zero};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        ledger::client_manager::ClientAccountManager,
+        model::{ClientId, TxId},
+    };
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+    pub enum LedgerAccount {
+        ClientAvailable(ClientId),
+        ClientHeld(ClientId),
+        /// Funds moving in and out of the system via deposits and withdrawals.
+        Settlement,
+        /// Funds reversed out of a client account by a chargeback.
+        ChargebackLoss,
+    }
+
+    /// A single balanced posting: `amount` is debited from one account and credited to another.
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct JournalEntry {
+        /// Input position of the record that made the posting; see
+        /// [`PaymentsEngine::position`](crate::ledger::engine::PaymentsEngine::position).
+        #[serde(default)]
+        pub position: u64,
+        pub tx: TxId,
+        pub debit: LedgerAccount,
+        pub credit: LedgerAccount,
+        #[serde(
+            serialize_with = "crate::output::serialise_decimal",
+            deserialize_with = "crate::output::deserialise_decimal"
+        )]
+        pub amount: BigDecimal,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub enum JournalError {
+        #[error("trial balance is out by {0}")]
+        Unbalanced(BigDecimal),
+        #[error("journal balance for {account:?} is {journal} but the account holds {actual}")]
+        Mismatch {
+            account: LedgerAccount,
+            journal: BigDecimal,
+            actual: BigDecimal,
+        },
+    }
+
+    #[derive(Default, Debug, Serialize, Deserialize)]
+    #[serde(transparent)]
+    pub struct Journal {
+        pub(crate) entries: Vec<JournalEntry>,
+    }
+
+    impl Journal {
+        pub fn post(
+            &mut self,
+            position: u64,
+            tx: TxId,
+            debit: LedgerAccount,
+            credit: LedgerAccount,
+            amount: BigDecimal,
+        ) {
+            self.entries.push(JournalEntry {
+                position,
+                tx,
+                debit,
+                credit,
+                amount,
+            });
+        }
+
+        pub fn entries(&self) -> &[JournalEntry] {
+            &self.entries
+        }
+
+        /// Balance of every account touched by the journal, as credits less debits.
+        pub fn balances(&self) -> BTreeMap<LedgerAccount, BigDecimal> {
+            let mut balances = BTreeMap::<LedgerAccount, BigDecimal>::new();
+            for entry in &self.entries {
+                *balances.entry(entry.debit).or_insert_with(zero) -= &entry.amount;
+                *balances.entry(entry.credit).or_insert_with(zero) += &entry.amount;
+            }
+            balances
+        }
+
+        pub fn balance(&self, account: LedgerAccount) -> BigDecimal {
+            self.balances().remove(&account).unwrap_or_else(zero)
+        }
+
+        /// Every posting is balanced, so the sum over all accounts must be zero.
+        pub fn trial_balance(&self) -> Result<(), JournalError> {
+            let sum = self
+                .balances()
+                .into_values()
+                .fold(zero::<BigDecimal>(), |acc, balance| acc + balance);
+
+            if sum == zero::<BigDecimal>() {
+                Ok(())
+            } else {
+                Err(JournalError::Unbalanced(sum))
+            }
+        }
+
+        /// Confirms the client balances derived from the journal match the account manager.
+        pub fn reconcile(&self, clients: &ClientAccountManager) -> Result<(), JournalError> {
+            self.trial_balance()?;
+
+            let balances = self.balances();
+            let derived = |account| balances.get(&account).cloned().unwrap_or_else(zero);
+
+            for (client, account) in &clients.accounts {
+                for (ledger_account, actual) in [
+                    (LedgerAccount::ClientAvailable(*client), &account.available),
+                    (LedgerAccount::ClientHeld(*client), &account.held),
+                ] {
+                    let journal = derived(ledger_account);
+                    if &journal != actual {
+                        return Err(JournalError::Mismatch {
+                            account: ledger_account,
+                            journal,
+                            actual: actual.clone(),
+                        });
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use bigdecimal::{BigDecimal, FromPrimitive as _, num_traits::zero};
+
+        use crate::ledger::journal::{Journal, LedgerAccount};
+
+        #[test]
+        fn test_balances_are_derived_from_postings() {
+            let mut journal = Journal::default();
+            let amount = BigDecimal::from_f32(10.0).unwrap();
+
+            journal.post(
+                1,
+                1.into(),
+                LedgerAccount::Settlement,
+                LedgerAccount::ClientAvailable(1.into()),
+                amount.clone(),
+            );
+            journal.post(
+                1,
+                1.into(),
+                LedgerAccount::ClientAvailable(1.into()),
+                LedgerAccount::ClientHeld(1.into()),
+                amount.clone(),
+            );
+
+            assert_eq!(
+                journal.balance(LedgerAccount::ClientAvailable(1.into())),
+                zero()
+            );
+            assert_eq!(journal.balance(LedgerAccount::ClientHeld(1.into())), amount);
+            assert_eq!(journal.balance(LedgerAccount::Settlement), -amount);
+            assert!(journal.trial_balance().is_ok());
         }
     }
 }
 
 pub mod engine {
-    use bigdecimal::num_traits::zero;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use bigdecimal::{Zero as _, num_traits::zero};
+    use serde::{Deserialize, Serialize};
 
     use crate::{
+        events::{Balances, EngineEvent, EngineObserver, EventKind},
+        history::{BalanceHistory, HistoryChanges},
         ledger::{
-            client_manager::{ClientAccountManager, ClientAccountStatus},
+            client_manager::{ClientAccount, ClientAccountManager, ClientAccountStatus},
+            journal::{Journal, JournalEntry, LedgerAccount},
             tx_manager::TxManager,
         },
-        model::{CSVRecord, TxType},
-        transaction::{Transaction, TransactionError, TransactionStatus, TxResult},
+        model::{CSVRecord, ClientId, TxId, TxType},
+        precision::PrecisionPolicy,
+        transaction::{BatchError, Transaction, TransactionError, TransactionStatus, TxResult},
     };
 
-    #[derive(Default)]
+    /// Serialises everything the engine has accumulated, so it can be snapshotted and restored.
+    /// The precision policy and observers are configuration rather than state and are not
+    /// included.
+    #[derive(Default, Serialize, Deserialize)]
     pub struct PaymentsEngine {
         pub client_manager: ClientAccountManager,
         pub tx_manager: TxManager,
+        pub journal: Journal,
+        /// Input records offered to the engine so far; see [`PaymentsEngine::set_position`].
+        #[serde(default)]
+        position: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        history: Option<BalanceHistory>,
+        #[serde(skip)]
+        precision: PrecisionPolicy,
+        #[serde(skip)]
+        observers: Vec<Box<dyn EngineObserver + Send>>,
+        /// Events held back while a batch may still be rolled back.
+        #[serde(skip)]
+        held_events: Option<Vec<EngineEvent>>,
+        /// Set between [`begin`](PaymentsEngine::begin) and its commit or roll back.
+        #[serde(skip)]
+        savepoint: Option<Rollback>,
+        /// Set by [`track_changes`](PaymentsEngine::track_changes).
+        #[serde(skip)]
+        unsaved: Option<Unsaved>,
+    }
+
+    /// What has been touched since changes were last taken.
+    struct Unsaved {
+        accounts: BTreeSet<ClientId>,
+        transactions: BTreeSet<TxId>,
+        journal_len: usize,
+        position: u64,
+    }
+
+    /// Everything an engine changed between two calls to
+    /// [`take_changes`](PaymentsEngine::take_changes), to be applied to the engine as it was at
+    /// the first with [`apply_changes`](PaymentsEngine::apply_changes).
+    #[derive(Serialize, Deserialize)]
+    pub struct EngineChanges {
+        accounts: BTreeMap<ClientId, ClientAccount>,
+        transactions: BTreeMap<TxId, Transaction>,
+        journal: Vec<JournalEntry>,
+        position: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        history: Option<HistoryChanges>,
+    }
+
+    /// What a batch has changed, captured before it first touched each account and transaction.
+    /// `None` means it did not exist yet. The journal is append-only, so its length suffices.
+    struct Rollback {
+        accounts: BTreeMap<ClientId, Option<ClientAccount>>,
+        transactions: BTreeMap<TxId, Option<Transaction>>,
+        journal_len: usize,
+        /// Position of the batch's first record.
+        position: u64,
+    }
+
+    impl Rollback {
+        fn restore(self, engine: &mut PaymentsEngine) {
+            if let Some(history) = &mut engine.history {
+                history.rewind(self.position);
+            }
+            for (client, account) in self.accounts {
+                match account {
+                    Some(account) => engine.client_manager.accounts.insert(client, account),
+                    None => engine.client_manager.accounts.remove(&client),
+                };
+            }
+            for (tx, transaction) in self.transactions {
+                match transaction {
+                    Some(transaction) => engine.tx_manager.transactions.insert(tx, transaction),
+                    None => engine.tx_manager.transactions.remove(&tx),
+                };
+            }
+            engine.journal.entries.truncate(self.journal_len);
+        }
     }
 
     impl PaymentsEngine {
+        pub fn with_precision(precision: PrecisionPolicy) -> Self {
+            Self {
+                precision,
+                ..Self::default()
+            }
+        }
+
+        pub fn precision(&self) -> &PrecisionPolicy {
+            &self.precision
+        }
+
+        pub fn set_precision(&mut self, precision: PrecisionPolicy) {
+            self.precision = precision;
+        }
+
+        /// How many input records the engine has been offered, counting rejected ones. The
+        /// balance history is keyed by this position.
+        pub fn position(&self) -> u64 {
+            self.position
+        }
+
+        /// Sets the position of the last record, for callers that reject some records before
+        /// they reach the engine and want positions to stay input record numbers.
+        pub fn set_position(&mut self, position: u64) {
+            self.position = position;
+        }
+
+        /// Starts keeping a [`BalanceHistory`] from the current balances on, so balances after
+        /// any later record can be queried. Does nothing if one is already kept.
+        pub fn keep_history(&mut self) {
+            if self.history.is_none() {
+                let balances = self
+                    .client_manager
+                    .accounts
+                    .iter()
+                    .map(|(client, account)| (*client, Balances::from(account)));
+                self.history = Some(BalanceHistory::new(self.position, balances));
+            }
+        }
+
+        pub fn history(&self) -> Option<&BalanceHistory> {
+            self.history.as_ref()
+        }
+
+        /// Registers `observer` to receive an [`EngineEvent`] for every change and rejection.
+        pub fn subscribe(&mut self, observer: impl EngineObserver + Send + 'static) {
+            self.observers.push(Box::new(observer));
+        }
+
+        fn balances(&self, client: ClientId) -> Balances {
+            self.client_manager
+                .accounts
+                .get(&client)
+                .map(Balances::from)
+                .unwrap_or_default()
+        }
+
+        fn notify(&mut self, event: &EngineEvent) {
+            for observer in &mut self.observers {
+                observer.on_event(event);
+            }
+        }
+
+        fn emit(&mut self, event: EngineEvent) {
+            match &mut self.held_events {
+                Some(held) => held.push(event),
+                None => self.notify(&event),
+            }
+        }
+
+        /// What an accepted record changed, given the tx status before it was applied.
+        fn changes(
+            &self,
+            tx: TxId,
+            r#type: TxType,
+            status: Option<TransactionStatus>,
+            before: &Balances,
+            after: &Balances,
+        ) -> Vec<EventKind> {
+            let Some(transaction) = self.tx_manager.get(tx) else {
+                return vec![EventKind::Ignored { operation: r#type }];
+            };
+            let amount = transaction.amount.clone();
+            let changed = status.as_ref() != Some(&transaction.status);
+            match r#type {
+                TxType::Deposit => vec![EventKind::Deposited { amount }],
+                TxType::Withdrawal => vec![EventKind::Withdrawn { amount }],
+                TxType::Dispute if changed => vec![EventKind::DisputeOpened { amount }],
+                TxType::Resolve if changed => vec![EventKind::DisputeResolved { amount }],
+                TxType::Chargeback if changed => {
+                    let mut kinds = vec![EventKind::ChargedBack { amount }];
+                    if !before.locked && after.locked {
+                        kinds.push(EventKind::AccountLocked);
+                    }
+                    kinds
+                }
+                operation => vec![EventKind::Ignored { operation }],
+            }
+        }
+
         fn process_deposit(&mut self, record: CSVRecord) -> TxResult {
             if self.tx_manager.exists(record.tx) {
-                return Err(TransactionError::DuplicateTransactionId(record.tx));
+                return Err(TransactionError::DuplicateTransactionId {
+                    client: record.client,
+                    tx: record.tx,
+                });
             }
 
             let account = self.client_manager.get_or_initialise(record.client);
             if account.is_locked() {
-                return Err(TransactionError::AccountLocked);
+                return Err(TransactionError::AccountLocked {
+                    client: record.client,
+                    tx: record.tx,
+                });
             }
 
             let tx = Transaction::try_from(record)?;
             account.available += &tx.amount;
+            self.journal.post(
+                self.position,
+                tx.tx,
+                LedgerAccount::Settlement,
+                LedgerAccount::ClientAvailable(tx.client),
+                tx.amount.clone(),
+            );
             self.tx_manager.insert(tx);
 
             Ok(())
@@ -208,20 +599,39 @@ pub mod engine {
 
         fn process_withdrawal(&mut self, record: CSVRecord) -> TxResult {
             if self.tx_manager.exists(record.tx) {
-                return Err(TransactionError::DuplicateTransactionId(record.tx));
+                return Err(TransactionError::DuplicateTransactionId {
+                    client: record.client,
+                    tx: record.tx,
+                });
             }
 
             let account = self.client_manager.get_or_initialise(record.client);
             if account.is_locked() {
-                return Err(TransactionError::AccountLocked);
+                return Err(TransactionError::AccountLocked {
+                    client: record.client,
+                    tx: record.tx,
+                });
             }
 
-            if &account.available < record.amount.as_ref().unwrap_or(&zero()) {
-                return Err(TransactionError::InsufficientFunds);
+            let requested = record.amount.clone().unwrap_or_else(zero);
+            if account.available < requested {
+                return Err(TransactionError::InsufficientFunds {
+                    client: record.client,
+                    tx: record.tx,
+                    requested,
+                    available: account.available.clone(),
+                });
             }
 
             let tx = Transaction::try_from(record)?;
             account.available -= &tx.amount;
+            self.journal.post(
+                self.position,
+                tx.tx,
+                LedgerAccount::ClientAvailable(tx.client),
+                LedgerAccount::Settlement,
+                tx.amount.clone(),
+            );
             self.tx_manager.insert(tx);
 
             Ok(())
@@ -229,10 +639,17 @@ pub mod engine {
 
         fn process_dispute(&mut self, record: CSVRecord) -> TxResult {
             let Some(transaction) = self.tx_manager.get(record.tx) else {
-                return Err(TransactionError::MissingTransaction(record.tx));
+                return Err(TransactionError::MissingTransaction {
+                    client: record.client,
+                    tx: record.tx,
+                });
             };
             if transaction.client != record.client {
-                return Err(TransactionError::InvalidClinetId);
+                return Err(TransactionError::ClientMismatch {
+                    tx: record.tx,
+                    expected: transaction.client,
+                    actual: record.client,
+                });
             }
             if transaction.r#type != TxType::Deposit || !transaction.can_be_disputed(&record) {
                 return Ok(());
@@ -241,15 +658,35 @@ pub mod engine {
             let account = self.client_manager.get_or_initialise(record.client);
             account.available -= &transaction.amount;
             account.held += &transaction.amount;
-
-            self.tx_manager
-                .set_status(transaction.tx, TransactionStatus::Disputed)
+            self.journal.post(
+                self.position,
+                transaction.tx,
+                LedgerAccount::ClientAvailable(transaction.client),
+                LedgerAccount::ClientHeld(transaction.client),
+                transaction.amount.clone(),
+            );
+
+            self.tx_manager.set_status(
+                transaction.client,
+                transaction.tx,
+                TransactionStatus::Disputed,
+            )
         }
 
         fn process_resolve(&mut self, record: CSVRecord) -> TxResult {
             let Some(transaction) = self.tx_manager.get(record.tx) else {
-                return Err(TransactionError::MissingTransaction(record.tx));
+                return Err(TransactionError::MissingTransaction {
+                    client: record.client,
+                    tx: record.tx,
+                });
             };
+            if transaction.client != record.client {
+                return Err(TransactionError::ClientMismatch {
+                    tx: record.tx,
+                    expected: transaction.client,
+                    actual: record.client,
+                });
+            }
             if !transaction.is_disputed() {
                 return Ok(());
             }
@@ -257,17 +694,34 @@ pub mod engine {
             let account = self.client_manager.get_or_initialise(record.client);
             account.available += &transaction.amount;
             account.held -= &transaction.amount;
-
-            self.tx_manager
-                .set_status(transaction.tx, TransactionStatus::Resolved)
+            self.journal.post(
+                self.position,
+                transaction.tx,
+                LedgerAccount::ClientHeld(transaction.client),
+                LedgerAccount::ClientAvailable(transaction.client),
+                transaction.amount.clone(),
+            );
+
+            self.tx_manager.set_status(
+                transaction.client,
+                transaction.tx,
+                TransactionStatus::Resolved,
+            )
         }
 
         fn process_chargeback(&mut self, record: CSVRecord) -> TxResult {
             let Some(transaction) = self.tx_manager.get(record.tx) else {
-                return Err(TransactionError::MissingTransaction(record.tx));
+                return Err(TransactionError::MissingTransaction {
+                    client: record.client,
+                    tx: record.tx,
+                });
             };
             if transaction.client != record.client {
-                return Err(TransactionError::InvalidClinetId);
+                return Err(TransactionError::ClientMismatch {
+                    tx: record.tx,
+                    expected: transaction.client,
+                    actual: record.client,
+                });
             }
             if !transaction.is_disputed() || transaction.r#type != TxType::Deposit {
                 return Ok(());
@@ -276,12 +730,104 @@ pub mod engine {
             let account = self.client_manager.get_or_initialise(record.client);
             account.status = ClientAccountStatus::Locked;
             account.held -= &transaction.amount;
-
-            self.tx_manager
-                .set_status(transaction.tx, TransactionStatus::Chargedback)
+            self.journal.post(
+                self.position,
+                transaction.tx,
+                LedgerAccount::ClientHeld(transaction.client),
+                LedgerAccount::ChargebackLoss,
+                transaction.amount.clone(),
+            );
+
+            self.tx_manager.set_status(
+                transaction.client,
+                transaction.tx,
+                TransactionStatus::Chargedback,
+            )
         }
 
+        /// Applies one record, emitting its events when anyone has subscribed and recording
+        /// its effect in the history when one is kept.
         pub fn process_csv_record(&mut self, record: CSVRecord) -> TxResult {
+            if let Some(savepoint) = &mut self.savepoint {
+                // A record only ever changes its own client's account and its own tx
+                savepoint
+                    .accounts
+                    .entry(record.client)
+                    .or_insert_with(|| self.client_manager.accounts.get(&record.client).cloned());
+                savepoint
+                    .transactions
+                    .entry(record.tx)
+                    .or_insert_with(|| self.tx_manager.get(record.tx).cloned());
+            }
+            if let Some(unsaved) = &mut self.unsaved {
+                unsaved.accounts.insert(record.client);
+                unsaved.transactions.insert(record.tx);
+            }
+            self.position += 1;
+            if self.observers.is_empty() && self.history.is_none() {
+                return self.apply_record(record);
+            }
+
+            let (client, tx, r#type) = (record.client, record.tx, record.r#type.clone());
+            let existed = self.client_manager.accounts.contains_key(&client);
+            let before = self.balances(client);
+            let status = self.tx_manager.get(tx).map(|t| t.status.clone());
+            let result = self.apply_record(record);
+            let after = self.balances(client);
+
+            if let Some(history) = &mut self.history {
+                // A rejected record can still open an empty account
+                if before != after || !existed && self.client_manager.accounts.contains_key(&client)
+                {
+                    history.record(client, self.position);
+                }
+                history.snapshot_if_due(self.position, &self.client_manager);
+            }
+            if self.observers.is_empty() {
+                return result;
+            }
+
+            let kinds = match &result {
+                Ok(()) => self.changes(tx, r#type, status, &before, &after),
+                Err(error) => vec![EventKind::Rejected {
+                    error: error.clone(),
+                }],
+            };
+            for kind in kinds {
+                self.emit(EngineEvent {
+                    position: self.position,
+                    client,
+                    tx,
+                    kind,
+                    before: before.clone(),
+                    after: after.clone(),
+                });
+            }
+            result
+        }
+
+        fn apply_record(&mut self, mut record: CSVRecord) -> TxResult {
+            if let Some(amount) = record.amount.take() {
+                let Some(rounded) = self.precision.apply(&amount) else {
+                    return Err(TransactionError::ExcessPrecision {
+                        client: record.client,
+                        tx: record.tx,
+                        amount,
+                        scale: self.precision.scale,
+                    });
+                };
+                // Checked after rounding, as an amount too small for the scale rounds to zero
+                let moves_funds = matches!(record.r#type, TxType::Deposit | TxType::Withdrawal);
+                if moves_funds && rounded.is_zero() {
+                    return Err(TransactionError::ZeroAmount {
+                        client: record.client,
+                        tx: record.tx,
+                        amount: amount.to_string(),
+                    });
+                }
+                record.amount = Some(rounded);
+            }
+
             match record.r#type {
                 TxType::Deposit => self.process_deposit(record),
                 TxType::Withdrawal => self.process_withdrawal(record),
@@ -290,6 +836,121 @@ pub mod engine {
                 TxType::Chargeback => self.process_chargeback(record),
             }
         }
+
+        /// Starts tracking changes, so that every account, transaction and journal change from
+        /// here on can be undone by [`roll_back`](Self::roll_back) until [`commit`](Self::commit).
+        /// Observers see the events in between only once they are committed.
+        pub fn begin(&mut self) {
+            debug_assert!(self.savepoint.is_none(), "savepoints do not nest");
+            self.savepoint = Some(Rollback {
+                accounts: BTreeMap::new(),
+                transactions: BTreeMap::new(),
+                journal_len: self.journal.entries.len(),
+                position: self.position + 1,
+            });
+            self.held_events = (!self.observers.is_empty()).then(Vec::new);
+        }
+
+        /// Keeps every change since [`begin`](Self::begin) and delivers its events.
+        pub fn commit(&mut self) {
+            self.savepoint = None;
+            for event in self.held_events.take().into_iter().flatten() {
+                self.notify(&event);
+            }
+        }
+
+        /// Undoes every change since [`begin`](Self::begin), dropping its events. The position
+        /// still counts the records offered in between.
+        pub fn roll_back(&mut self) {
+            self.held_events = None;
+            if let Some(savepoint) = self.savepoint.take() {
+                savepoint.restore(self);
+            }
+        }
+
+        /// Starts tracking changes from the current state on, so they can be saved with
+        /// [`take_changes`](Self::take_changes) rather than by serialising the whole engine.
+        pub fn track_changes(&mut self) {
+            self.unsaved = Some(Unsaved {
+                accounts: BTreeSet::new(),
+                transactions: BTreeSet::new(),
+                journal_len: self.journal.entries.len(),
+                position: self.position,
+            });
+        }
+
+        /// The accounts and transactions touched since tracking started or changes were last
+        /// taken, with the journal entries and history added since, or `None` if changes are not
+        /// tracked. Not to be called inside a batch.
+        pub fn take_changes(&mut self) -> Option<EngineChanges> {
+            let unsaved = self.unsaved.take()?;
+            let entries = &self.journal.entries;
+            let changes = EngineChanges {
+                // Missing ones were opened and rolled back since, so were never saved either
+                accounts: unsaved
+                    .accounts
+                    .into_iter()
+                    .filter_map(|client| {
+                        Some((client, self.client_manager.accounts.get(&client)?.clone()))
+                    })
+                    .collect(),
+                transactions: unsaved
+                    .transactions
+                    .into_iter()
+                    .filter_map(|tx| Some((tx, self.tx_manager.get(tx)?.clone())))
+                    .collect(),
+                journal: entries[unsaved.journal_len.min(entries.len())..].to_vec(),
+                position: self.position,
+                history: (self.history.as_ref()).map(|history| history.since(unsaved.position)),
+            };
+            self.track_changes();
+            Some(changes)
+        }
+
+        /// Brings an engine restored from an earlier state up to date with `changes`.
+        pub fn apply_changes(&mut self, changes: EngineChanges) {
+            self.client_manager.accounts.extend(changes.accounts);
+            self.tx_manager.transactions.extend(changes.transactions);
+            self.journal.entries.extend(changes.journal);
+            self.position = changes.position;
+            if let Some(history) = changes.history {
+                self.history.get_or_insert_default().apply(history);
+            }
+        }
+
+        /// Applies `records` all-or-nothing: if any is rejected, every account, transaction and
+        /// journal change made by the batch is undone. Observers see the batch's events only
+        /// once it has applied, or just the rejection if it was rolled back.
+        pub fn process_batch(
+            &mut self,
+            records: impl IntoIterator<Item = CSVRecord>,
+        ) -> Result<(), BatchError> {
+            self.begin();
+            for (index, record) in records.into_iter().enumerate() {
+                let (client, tx) = (record.client, record.tx);
+                if let Err(error) = self.process_csv_record(record) {
+                    let position = self.position;
+                    let notify = self.held_events.is_some();
+                    self.roll_back();
+                    if notify {
+                        let balances = self.balances(client);
+                        self.notify(&EngineEvent {
+                            position,
+                            client,
+                            tx,
+                            kind: EventKind::Rejected {
+                                error: error.clone(),
+                            },
+                            before: balances.clone(),
+                            after: balances,
+                        });
+                    }
+                    return Err(BatchError { index, error });
+                }
+            }
+            self.commit();
+            Ok(())
+        }
     }
 
     #[cfg(test)]
@@ -298,8 +959,9 @@ pub mod engine {
 
         use crate::{
             file_reader::csv_stream,
-            ledger::engine::PaymentsEngine,
+            ledger::{engine::PaymentsEngine, journal::LedgerAccount},
             model::{CSVRecord, TxType},
+            precision::PrecisionPolicy,
             transaction::TransactionError,
         };
 
@@ -340,7 +1002,15 @@ withdrawal,  1,  2,  200.0
 
                 let result = payment_engine.process_csv_record(record);
                 if idx == 1 {
-                    assert!(matches!(result, Err(TransactionError::InsufficientFunds)));
+                    assert_eq!(
+                        result,
+                        Err(TransactionError::InsufficientFunds {
+                            client: 1.into(),
+                            tx: 2.into(),
+                            requested: "200".parse().unwrap(),
+                            available: "100".parse().unwrap(),
+                        })
+                    );
                 }
             }
 
@@ -374,7 +1044,7 @@ resolve,1,1,
                     assert!(result.is_err());
                     assert!(matches!(
                         result.unwrap_err(),
-                        TransactionError::MissingTransaction(_)
+                        TransactionError::MissingTransaction { .. }
                     ))
                 } else {
                     assert!(result.is_ok());
@@ -407,7 +1077,7 @@ deposit,1,2,100.0
                     assert!(result.is_err());
                     assert!(matches!(
                         result.unwrap_err(),
-                        TransactionError::AccountLocked
+                        TransactionError::AccountLocked { .. }
                     ))
                 } else {
                     assert!(result.is_ok());
@@ -425,6 +1095,129 @@ deposit,1,2,100.0
             assert_eq!(payment_engine.tx_manager.tx_count(), 1);
         }
 
+        #[test]
+        fn test_journal_reconciles_with_accounts() {
+            let test_data = r#" type,  client,  tx,  amount
+deposit,1,1,100.0
+deposit,2,2,50.0
+withdrawal,2,3,20.0
+dispute,1,1,
+resolve,1,1,
+dispute,1,1,
+chargeback,1,1,
+dispute,2,2,
+"#;
+
+            let mut payment_engine = PaymentsEngine::default();
+            for record in csv_stream(test_data.as_bytes()) {
+                payment_engine.process_csv_record(record.unwrap()).unwrap();
+            }
+
+            let journal = &payment_engine.journal;
+            assert_eq!(journal.entries().len(), 8);
+            assert!(journal.reconcile(&payment_engine.client_manager).is_ok());
+            assert_eq!(
+                journal.balance(LedgerAccount::ChargebackLoss),
+                BigDecimal::from_f32(100.0).unwrap()
+            );
+            assert_eq!(
+                journal.balance(LedgerAccount::Settlement),
+                BigDecimal::from_f32(-130.0).unwrap()
+            );
+        }
+
+        #[test]
+        fn test_precision_policy_applies_before_processing() {
+            let test_data = r#" type,  client,  tx,  amount
+deposit,1,1,1.00005
+deposit,1,2,2.5
+"#;
+
+            let mut payment_engine = PaymentsEngine::default();
+            for record in csv_stream(test_data.as_bytes()) {
+                payment_engine.process_csv_record(record.unwrap()).unwrap();
+            }
+            let total = payment_engine
+                .client_manager
+                .get_or_initialise(1.into())
+                .total();
+            assert_eq!(total, "3.5000".parse().unwrap());
+
+            let mut payment_engine = PaymentsEngine::with_precision(PrecisionPolicy {
+                reject_excess: true,
+                ..PrecisionPolicy::default()
+            });
+            let results = csv_stream(test_data.as_bytes())
+                .map(|record| payment_engine.process_csv_record(record.unwrap()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                results,
+                vec![
+                    Err(TransactionError::ExcessPrecision {
+                        client: 1.into(),
+                        tx: 1.into(),
+                        amount: "1.00005".parse().unwrap(),
+                        scale: 4,
+                    }),
+                    Ok(())
+                ]
+            );
+            assert_eq!(payment_engine.tx_manager.tx_count(), 1);
+
+            // Too small to survive rounding, so rejected rather than opening an empty account
+            let mut payment_engine = PaymentsEngine::default();
+            let result = payment_engine.process_csv_record(CSVRecord {
+                r#type: TxType::Deposit,
+                client: 1.into(),
+                tx: 1.into(),
+                amount: Some("0.00001".parse().unwrap()),
+            });
+            assert!(matches!(result, Err(TransactionError::ZeroAmount { .. })));
+            assert!(payment_engine.client_manager.accounts.is_empty());
+        }
+
+        #[test]
+        fn test_failed_batch_is_rolled_back() {
+            let mut payment_engine = PaymentsEngine::default();
+            let setup = "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,2,2,5.0\n";
+            for record in csv_stream(setup.as_bytes()) {
+                payment_engine.process_csv_record(record.unwrap()).unwrap();
+            }
+            let state = |engine: &PaymentsEngine| {
+                serde_json::to_string(&(
+                    &engine.client_manager,
+                    &engine.tx_manager,
+                    &engine.journal,
+                ))
+                .unwrap()
+            };
+            let before = state(&payment_engine);
+
+            // Touches an existing account, creates a new one, disputes, then overdraws
+            let batch = r#"type,client,tx,amount
+deposit,1,3,2.5
+deposit,3,4,1.0
+dispute,1,1,
+withdrawal,2,5,6.0
+"#;
+            let records = csv_stream(batch.as_bytes()).map(Result::unwrap);
+            let err = payment_engine.process_batch(records).unwrap_err();
+            assert_eq!(err.index, 3);
+            assert_eq!(err.error.code(), "insufficient_funds");
+            assert_eq!(state(&payment_engine), before);
+
+            // The same txs apply once the batch is fixed
+            let fixed = batch.replace("withdrawal,2,5,6.0", "withdrawal,2,5,4.0");
+            let records = csv_stream(fixed.as_bytes()).map(Result::unwrap);
+            payment_engine.process_batch(records).unwrap();
+            assert_eq!(payment_engine.tx_manager.tx_count(), 5);
+            assert!(payment_engine.tx_manager.is_disputed(1.into()));
+            payment_engine
+                .journal
+                .reconcile(&payment_engine.client_manager)
+                .unwrap();
+        }
+
         #[test]
         fn test_non_matching_client_ids() {
             let test_data = r#" type,  client,  tx,  amount
@@ -483,3 +1276,208 @@ withdrawal,1,2,100.0
         }
     }
 }
+
+pub mod invariants {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use bigdecimal::{BigDecimal, num_traits::zero};
+
+    use crate::{
+        ledger::{engine::PaymentsEngine, journal::LedgerAccount},
+        model::{ClientId, TxId},
+        transaction::TransactionStatus,
+    };
+
+    #[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+    pub enum InvariantViolation {
+        #[error("client {client} has a total of {total} but its postings sum to {posted}")]
+        TotalMismatch {
+            client: ClientId,
+            total: BigDecimal,
+            posted: BigDecimal,
+        },
+        #[error("client {client} holds {held} but has {disputed} under dispute")]
+        HeldMismatch {
+            client: ClientId,
+            held: BigDecimal,
+            disputed: BigDecimal,
+        },
+        #[error("client {0} is locked without a chargeback")]
+        LockedWithoutChargeback(ClientId),
+        #[error("TxId={0} changed after being charged back")]
+        ChargebackReopened(TxId),
+        #[error(transparent)]
+        Journal(#[from] crate::ledger::journal::JournalError),
+    }
+
+    /// Checks ledger invariants against an engine. Stateful so that it can confirm
+    /// charged back transactions never change between calls to [`InvariantChecker::check`].
+    #[derive(Default)]
+    pub struct InvariantChecker {
+        chargedback: BTreeSet<TxId>,
+    }
+
+    impl InvariantChecker {
+        pub fn check(&mut self, engine: &PaymentsEngine) -> Result<(), InvariantViolation> {
+            let mut disputed = BTreeMap::<ClientId, BigDecimal>::new();
+            let mut chargedback_clients = BTreeSet::new();
+
+            for transaction in engine.tx_manager.iter() {
+                match transaction.status {
+                    TransactionStatus::Disputed => {
+                        *disputed.entry(transaction.client).or_insert_with(zero) +=
+                            &transaction.amount;
+                    }
+                    TransactionStatus::Chargedback => {
+                        chargedback_clients.insert(transaction.client);
+                    }
+                    _ => {}
+                }
+            }
+
+            for tx in &self.chargedback {
+                let unchanged = engine
+                    .tx_manager
+                    .get(*tx)
+                    .is_some_and(|t| t.status == TransactionStatus::Chargedback);
+                if !unchanged {
+                    return Err(InvariantViolation::ChargebackReopened(*tx));
+                }
+            }
+
+            // The total is derived from the journal rather than the account's own balances
+            let balances = engine.journal.balances();
+            let posted = |account| balances.get(&account).cloned().unwrap_or_else(zero);
+
+            for (client, account) in &engine.client_manager.accounts {
+                let total = account.total();
+                let posted = posted(LedgerAccount::ClientAvailable(*client))
+                    + posted(LedgerAccount::ClientHeld(*client));
+                if total != posted {
+                    return Err(InvariantViolation::TotalMismatch {
+                        client: *client,
+                        total,
+                        posted,
+                    });
+                }
+
+                let disputed = disputed.remove(client).unwrap_or_else(zero);
+                if account.held != disputed {
+                    return Err(InvariantViolation::HeldMismatch {
+                        client: *client,
+                        held: account.held.clone(),
+                        disputed,
+                    });
+                }
+
+                if account.is_locked() && !chargedback_clients.contains(client) {
+                    return Err(InvariantViolation::LockedWithoutChargeback(*client));
+                }
+            }
+
+            engine.journal.reconcile(&engine.client_manager)?;
+
+            self.chargedback.extend(
+                engine
+                    .tx_manager
+                    .iter()
+                    .filter(|t| t.status == TransactionStatus::Chargedback)
+                    .map(|t| t.tx),
+            );
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use bigdecimal::BigDecimal;
+        use proptest::prelude::*;
+
+        use crate::{
+            ledger::{
+                engine::PaymentsEngine,
+                invariants::{InvariantChecker, InvariantViolation},
+            },
+            model::{CSVRecord, TxType},
+        };
+
+        fn tx_type() -> impl Strategy<Value = TxType> {
+            prop_oneof![
+                4 => Just(TxType::Deposit),
+                3 => Just(TxType::Withdrawal),
+                2 => Just(TxType::Dispute),
+                1 => Just(TxType::Resolve),
+                1 => Just(TxType::Chargeback),
+            ]
+        }
+
+        fn csv_record() -> impl Strategy<Value = CSVRecord> {
+            (
+                tx_type(),
+                1u16..5,
+                1u32..40,
+                proptest::option::weighted(0.9, -1_000i64..1_000_000),
+            )
+                .prop_map(|(r#type, client, tx, amount)| CSVRecord {
+                    r#type,
+                    client: client.into(),
+                    tx: tx.into(),
+                    amount: amount.map(|amount| BigDecimal::new(amount.into(), 4)),
+                })
+        }
+
+        #[test]
+        fn test_detects_balances_that_disagree_with_the_journal() {
+            let mut payment_engine = PaymentsEngine::default();
+            payment_engine
+                .process_csv_record(CSVRecord {
+                    r#type: TxType::Deposit,
+                    client: 1.into(),
+                    tx: 1.into(),
+                    amount: Some(BigDecimal::new(10.into(), 0)),
+                })
+                .unwrap();
+            let mut checker = InvariantChecker::default();
+            assert_eq!(checker.check(&payment_engine), Ok(()));
+
+            let account = payment_engine.client_manager.accounts.get_mut(&1.into());
+            account.unwrap().available += BigDecimal::new(1.into(), 0);
+            assert!(matches!(
+                checker.check(&payment_engine),
+                Err(InvariantViolation::TotalMismatch { .. })
+            ));
+        }
+
+        proptest! {
+            #[test]
+            fn invariants_hold_after_every_record(
+                records in proptest::collection::vec(csv_record(), 0..200)
+            ) {
+                let mut payment_engine = PaymentsEngine::default();
+                let mut checker = InvariantChecker::default();
+
+                for record in records {
+                    let _ = payment_engine.process_csv_record(record);
+                    prop_assert_eq!(checker.check(&payment_engine), Ok(()));
+                }
+            }
+
+            #[test]
+            fn invariants_hold_with_a_single_client(
+                records in proptest::collection::vec(
+                    csv_record().prop_map(|mut r| { r.client = 1.into(); r }),
+                    0..200,
+                )
+            ) {
+                let mut payment_engine = PaymentsEngine::default();
+                let mut checker = InvariantChecker::default();
+
+                for record in records {
+                    let _ = payment_engine.process_csv_record(record);
+                    prop_assert_eq!(checker.check(&payment_engine), Ok(()));
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,17 @@
+pub mod checkpoint;
+pub mod events;
 pub mod file_reader;
+pub mod history;
+#[cfg(feature = "server")]
+pub mod ingest;
 pub mod ledger;
 pub mod model;
 pub mod output;
+pub mod precision;
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod statement;
 pub mod transaction;
+pub mod validation;
+pub mod workload;
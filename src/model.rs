@@ -1,6 +1,6 @@
 use std::fmt;
 
-use bigdecimal::{BigDecimal, RoundingMode};
+use bigdecimal::BigDecimal;
 use serde::{Deserialize, Deserializer, Serialize, de::Error};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -47,7 +47,7 @@ impl From<TxId> for u32 {
     }
 }
 
-#[derive(Clone, serde::Deserialize, Debug, PartialEq, Eq)]
+#[derive(Clone, serde::Deserialize, Serialize, Debug, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
 pub enum TxType {
     Deposit,
@@ -57,27 +57,43 @@ pub enum TxType {
     Chargeback,
 }
 
+impl TxType {
+    /// The lowercase name used in input files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TxType::Deposit => "deposit",
+            TxType::Withdrawal => "withdrawal",
+            TxType::Dispute => "dispute",
+            TxType::Resolve => "resolve",
+            TxType::Chargeback => "chargeback",
+        }
+    }
+}
+
+/// Parses an amount the way every input format should: whitespace is trimmed and empty means no
+/// amount. The value is kept exact; the engine's `PrecisionPolicy` decides how it is rounded.
+pub fn parse_decimal(string: &str) -> Result<Option<BigDecimal>, String> {
+    let string = string.trim();
+    if string.is_empty() {
+        return Ok(None);
+    }
+
+    BigDecimal::parse_bytes(string.as_bytes(), 10)
+        .map(Some)
+        .ok_or_else(|| format!("Unable to parse to BigDecimal: {string}"))
+}
+
 pub fn deserialize_decimal<'de, D>(deserializer: D) -> Result<Option<BigDecimal>, D::Error>
 where
     D: Deserializer<'de>,
 {
     match Option::<String>::deserialize(deserializer)? {
-        Some(string) => {
-            let string = string.trim();
-            if string.is_empty() {
-                return Ok(None);
-            }
-
-            let d = BigDecimal::parse_bytes(string.as_bytes(), 10)
-                .ok_or_else(|| Error::custom(format!("Unable to parse to BigDecimal: {string}")))?;
-
-            Ok(Some(d.with_scale_round(4, RoundingMode::HalfEven)))
-        }
+        Some(string) => parse_decimal(&string).map_err(Error::custom),
         None => Ok(None),
     }
 }
 
-#[derive(serde::Deserialize, Debug)]
+#[derive(serde::Deserialize, Serialize, Debug)]
 pub struct CSVRecord {
     pub r#type: TxType,
     pub client: ClientId,
@@ -85,3 +101,33 @@ pub struct CSVRecord {
     #[serde(default, deserialize_with = "deserialize_decimal")]
     pub amount: Option<BigDecimal>,
 }
+
+/// A record with its amount still as the source text, so strict validation can see how it was
+/// written before it is parsed.
+#[derive(serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RawRecord {
+    pub r#type: TxType,
+    pub client: ClientId,
+    pub tx: TxId,
+    #[serde(default)]
+    pub amount: Option<String>,
+    /// Consecutive records sharing a batch id are applied all-or-nothing.
+    #[serde(default)]
+    pub batch: Option<String>,
+}
+
+impl TryFrom<RawRecord> for CSVRecord {
+    type Error = String;
+
+    fn try_from(value: RawRecord) -> Result<Self, Self::Error> {
+        Ok(CSVRecord {
+            r#type: value.r#type,
+            client: value.client,
+            tx: value.tx,
+            amount: match value.amount {
+                Some(amount) => parse_decimal(&amount)?,
+                None => None,
+            },
+        })
+    }
+}
@@ -1,22 +1,82 @@
-use std::io;
+use std::{io, str::FromStr};
 
-use bigdecimal::{BigDecimal, RoundingMode};
-use serde::{Serialize, Serializer};
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
 
-use crate::{ledger::client_manager::ClientAccountManager, model::ClientId};
+use crate::{
+    events::{Balances, EngineEvent, EventKind},
+    ledger::client_manager::ClientAccountManager,
+    model::{ClientId, TxId, TxType},
+    precision::PrecisionPolicy,
+};
 
-fn serialise_decimal<S>(decimal: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialise_decimal<S>(decimal: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let rounded = decimal.with_scale_round(4, RoundingMode::HalfEven);
-    serializer.serialize_str(&rounded.to_string())
+    serializer.collect_str(decimal)
 }
 
-#[allow(dead_code)]
-#[derive(Serialize)]
+pub(crate) fn deserialise_decimal<'de, D>(deserializer: D) -> Result<BigDecimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let string = String::deserialize(deserializer)?;
+    BigDecimal::parse_bytes(string.as_bytes(), 10)
+        .ok_or_else(|| Error::custom(format!("Unable to parse to BigDecimal: {string}")))
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub struct OutputRecord {
     pub client: ClientId,
+    #[serde(
+        serialize_with = "serialise_decimal",
+        deserialize_with = "deserialise_decimal"
+    )]
+    pub available: BigDecimal,
+    #[serde(
+        serialize_with = "serialise_decimal",
+        deserialize_with = "deserialise_decimal"
+    )]
+    pub held: BigDecimal,
+    #[serde(
+        serialize_with = "serialise_decimal",
+        deserialize_with = "deserialise_decimal"
+    )]
+    pub total: BigDecimal,
+    pub locked: bool,
+}
+
+/// Account rows with balances rounded to the `display` precision, which may differ from the
+/// precision the engine works in.
+pub fn output_records<'a>(
+    clients: &'a ClientAccountManager,
+    display: &'a PrecisionPolicy,
+) -> impl Iterator<Item = OutputRecord> + 'a {
+    clients
+        .accounts
+        .iter()
+        .map(|(client, account)| OutputRecord {
+            client: *client,
+            available: display.round(&account.available),
+            held: display.round(&account.held),
+            total: display.round(&account.total()),
+            locked: account.is_locked(),
+        })
+}
+
+/// One applied record as a change-data-capture row: how it moved the client's balances and where
+/// they ended up, so an account's trajectory can be rebuilt without rerunning the engine.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct ChangeRecord {
+    pub seq: u64,
+    pub client: ClientId,
+    pub tx: TxId,
+    pub operation: &'static str,
+    #[serde(serialize_with = "serialise_decimal")]
+    pub available_change: BigDecimal,
+    #[serde(serialize_with = "serialise_decimal")]
+    pub held_change: BigDecimal,
     #[serde(serialize_with = "serialise_decimal")]
     pub available: BigDecimal,
     #[serde(serialize_with = "serialise_decimal")]
@@ -26,28 +86,225 @@ pub struct OutputRecord {
     pub locked: bool,
 }
 
-pub fn write_accounts_to_stdout(clients: &ClientAccountManager) -> Result<(), csv::Error> {
-    let stdout = io::stdout().lock();
-    let mut csv_wtr = csv::WriterBuilder::new()
+impl ChangeRecord {
+    /// The row for `event`, with amounts rounded to the `display` precision and `seq` set to the
+    /// record's engine position, or `None` for events that are not an applied record: rejections,
+    /// and the lock after a chargeback.
+    pub fn from_event(event: &EngineEvent, display: &PrecisionPolicy) -> Option<Self> {
+        let operation = match &event.kind {
+            EventKind::Deposited { .. } => TxType::Deposit,
+            EventKind::Withdrawn { .. } => TxType::Withdrawal,
+            EventKind::DisputeOpened { .. } => TxType::Dispute,
+            EventKind::DisputeResolved { .. } => TxType::Resolve,
+            EventKind::ChargedBack { .. } => TxType::Chargeback,
+            EventKind::Ignored { operation } => operation.clone(),
+            EventKind::AccountLocked | EventKind::Rejected { .. } => return None,
+        };
+        let (before, after) = (&event.before, &event.after);
+        Some(ChangeRecord {
+            seq: event.position,
+            client: event.client,
+            tx: event.tx,
+            operation: operation.name(),
+            available_change: display.round(&(&after.available - &before.available)),
+            held_change: display.round(&(&after.held - &before.held)),
+            available: display.round(&after.available),
+            held: display.round(&after.held),
+            total: display.round(&after.total),
+            locked: after.locked,
+        })
+    }
+}
+
+/// [`output_records`] for balances from the [`BalanceHistory`](crate::history::BalanceHistory).
+pub fn balance_records<'a>(
+    balances: impl Iterator<Item = (ClientId, &'a Balances)> + 'a,
+    display: &'a PrecisionPolicy,
+) -> impl Iterator<Item = OutputRecord> + 'a {
+    balances.map(|(client, balances)| OutputRecord {
+        client,
+        available: display.round(&balances.available),
+        held: display.round(&balances.held),
+        total: display.round(&balances.total),
+        locked: balances.locked,
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Json,
+    JsonLines,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "json" => Ok(OutputFormat::Json),
+            "jsonl" => Ok(OutputFormat::JsonLines),
+            _ => Err(format!(
+                "unknown output format `{s}`, expected csv, json or jsonl"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum OutputError {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Serialises records in the selected [`OutputFormat`]; call [`RecordWriter::finish`] once done.
+pub enum RecordWriter<W: io::Write> {
+    Csv(Box<csv::Writer<W>>),
+    Json { wtr: W, empty: bool },
+    JsonLines(W),
+}
+
+impl<W: io::Write> RecordWriter<W> {
+    pub fn new(format: OutputFormat, wtr: W) -> Self {
+        match format {
+            OutputFormat::Csv => RecordWriter::Csv(Box::new(
+                csv::WriterBuilder::new().has_headers(true).from_writer(wtr),
+            )),
+            OutputFormat::Json => RecordWriter::Json { wtr, empty: true },
+            OutputFormat::JsonLines => RecordWriter::JsonLines(wtr),
+        }
+    }
+
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<(), OutputError> {
+        match self {
+            RecordWriter::Csv(csv_wtr) => csv_wtr.serialize(record)?,
+            RecordWriter::Json { wtr, empty } => {
+                wtr.write_all(if *empty { b"[\n" } else { b",\n" })?;
+                *empty = false;
+                serde_json::to_writer(&mut *wtr, record)?;
+            }
+            RecordWriter::JsonLines(wtr) => {
+                serde_json::to_writer(&mut *wtr, record)?;
+                wtr.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), OutputError> {
+        match self {
+            RecordWriter::Csv(mut csv_wtr) => csv_wtr.flush()?,
+            RecordWriter::Json { mut wtr, empty } => {
+                wtr.write_all(if empty { b"[]\n" } else { b"\n]\n" })?;
+                wtr.flush()?;
+            }
+            RecordWriter::JsonLines(mut wtr) => wtr.flush()?,
+        }
+        Ok(())
+    }
+}
+
+pub fn write_accounts<W: io::Write>(
+    clients: &ClientAccountManager,
+    format: OutputFormat,
+    display: &PrecisionPolicy,
+    wtr: W,
+) -> Result<(), OutputError> {
+    let mut record_wtr = RecordWriter::new(format, wtr);
+
+    for account in output_records(clients, display) {
+        record_wtr.write(&account)?;
+    }
+
+    record_wtr.finish()
+}
+
+pub fn read_accounts<R: io::Read>(rdr: R) -> Result<Vec<OutputRecord>, csv::Error> {
+    csv::ReaderBuilder::new()
         .has_headers(true)
-        .from_writer(stdout);
+        .trim(csv::Trim::All)
+        .from_reader(rdr)
+        .into_deserialize()
+        .collect()
+}
 
-    let iter = clients
-        .accounts
-        .iter()
-        .map(move |(client, account)| OutputRecord {
-            client: *client,
-            available: account.available.clone(),
-            held: account.held.clone(),
-            total: account.total(),
-            locked: account.is_locked(),
-        });
+pub fn write_accounts_to_stdout(clients: &ClientAccountManager) -> Result<(), OutputError> {
+    write_accounts(
+        clients,
+        OutputFormat::Csv,
+        &PrecisionPolicy::default(),
+        io::stdout().lock(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::{BigDecimal, FromPrimitive as _};
 
-    for account in iter {
-        csv_wtr.serialize(account)?;
+    use crate::{
+        ledger::client_manager::ClientAccountManager,
+        output::{OutputFormat, write_accounts},
+        precision::PrecisionPolicy,
+    };
+
+    fn render(clients: &ClientAccountManager, format: OutputFormat) -> String {
+        let mut buffer = Vec::new();
+        write_accounts(clients, format, &PrecisionPolicy::default(), &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
     }
 
-    csv_wtr.flush()?;
+    #[test]
+    fn test_output_formats_keep_exact_decimals() {
+        let mut clients = ClientAccountManager::default();
+        clients.get_or_initialise(1.into()).available = BigDecimal::from_f32(1.5).unwrap();
+        clients.get_or_initialise(2.into()).held = "0.00015".parse().unwrap();
 
-    Ok(())
+        assert_eq!(
+            render(&clients, OutputFormat::Csv),
+            "client,available,held,total,locked\n1,1.5000,0,1.5000,false\n2,0,0.0002,0.0002,false\n"
+        );
+        assert_eq!(
+            render(&clients, OutputFormat::JsonLines),
+            r#"{"client":1,"available":"1.5000","held":"0","total":"1.5000","locked":false}
+{"client":2,"available":"0","held":"0.0002","total":"0.0002","locked":false}
+"#
+        );
+        assert_eq!(
+            render(&clients, OutputFormat::Json),
+            r#"[
+{"client":1,"available":"1.5000","held":"0","total":"1.5000","locked":false},
+{"client":2,"available":"0","held":"0.0002","total":"0.0002","locked":false}
+]
+"#
+        );
+        assert_eq!(
+            render(&ClientAccountManager::default(), OutputFormat::Json),
+            "[]\n"
+        );
+    }
+
+    #[test]
+    fn test_display_precision() {
+        let mut clients = ClientAccountManager::default();
+        clients.get_or_initialise(1.into()).available = "1.23456".parse().unwrap();
+
+        let mut buffer = Vec::new();
+        write_accounts(
+            &clients,
+            OutputFormat::Csv,
+            &PrecisionPolicy::with_scale(2),
+            &mut buffer,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "client,available,held,total,locked\n1,1.23,0,1.23,false\n"
+        );
+    }
 }
@@ -0,0 +1,91 @@
+use bigdecimal::{BigDecimal, RoundingMode};
+
+/// The most decimal places the binaries accept for a scale. Rounding gets slower as the scale
+/// grows, so an unbounded one could stall the engine.
+pub const MAX_SCALE: i64 = 28;
+
+/// How many decimal places amounts carry and what happens to input with more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecisionPolicy {
+    pub scale: i64,
+    pub rounding: RoundingMode,
+    /// Reject amounts with more than `scale` decimal places instead of rounding them.
+    pub reject_excess: bool,
+}
+
+impl Default for PrecisionPolicy {
+    fn default() -> Self {
+        Self {
+            scale: 4,
+            rounding: RoundingMode::HalfEven,
+            reject_excess: false,
+        }
+    }
+}
+
+impl PrecisionPolicy {
+    pub fn with_scale(scale: i64) -> Self {
+        Self {
+            scale,
+            ..Self::default()
+        }
+    }
+
+    pub fn round(&self, amount: &BigDecimal) -> BigDecimal {
+        amount.with_scale_round(self.scale, self.rounding)
+    }
+
+    /// Rounds `amount` to the policy's scale, or `None` when it has excess places and the policy
+    /// rejects them.
+    pub fn apply(&self, amount: &BigDecimal) -> Option<BigDecimal> {
+        let rounded = self.round(amount);
+        if self.reject_excess && &rounded != amount {
+            return None;
+        }
+        Some(rounded)
+    }
+}
+
+pub fn parse_rounding_mode(s: &str) -> Result<RoundingMode, String> {
+    match s {
+        "up" => Ok(RoundingMode::Up),
+        "down" => Ok(RoundingMode::Down),
+        "ceiling" => Ok(RoundingMode::Ceiling),
+        "floor" => Ok(RoundingMode::Floor),
+        "half-up" => Ok(RoundingMode::HalfUp),
+        "half-down" => Ok(RoundingMode::HalfDown),
+        "half-even" => Ok(RoundingMode::HalfEven),
+        _ => Err(format!(
+            "unknown rounding mode `{s}`, expected one of up, down, ceiling, floor, half-up, half-down, half-even"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bigdecimal::{BigDecimal, RoundingMode};
+
+    use crate::precision::PrecisionPolicy;
+
+    #[test]
+    fn test_round_or_reject_excess_precision() {
+        let amount = "1.23456".parse::<BigDecimal>().unwrap();
+
+        let policy = PrecisionPolicy::default();
+        assert_eq!(policy.apply(&amount), Some("1.2346".parse().unwrap()));
+
+        let policy = PrecisionPolicy {
+            scale: 2,
+            rounding: RoundingMode::Down,
+            reject_excess: false,
+        };
+        assert_eq!(policy.apply(&amount), Some("1.23".parse().unwrap()));
+
+        let policy = PrecisionPolicy {
+            reject_excess: true,
+            ..PrecisionPolicy::default()
+        };
+        assert_eq!(policy.apply(&amount), None);
+        assert!(policy.apply(&"1.2300000".parse().unwrap()).is_some());
+    }
+}
@@ -0,0 +1,81 @@
+use serde::Deserialize;
+
+/// A source column, either by header name or by 0-based position.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+/// Where each `CSVRecord` field is found in the source file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ColumnMapping {
+    pub r#type: Column,
+    pub client: Column,
+    pub tx: Column,
+    pub amount: Column,
+    /// Optional column grouping consecutive records into all-or-nothing batches.
+    #[serde(default)]
+    pub batch: Option<Column>,
+}
+
+impl ColumnMapping {
+    pub fn by_name() -> Self {
+        Self {
+            r#type: Column::Name("type".into()),
+            client: Column::Name("client".into()),
+            tx: Column::Name("tx".into()),
+            amount: Column::Name("amount".into()),
+            batch: None,
+        }
+    }
+
+    pub fn by_index() -> Self {
+        Self {
+            r#type: Column::Index(0),
+            client: Column::Index(1),
+            tx: Column::Index(2),
+            amount: Column::Index(3),
+            batch: None,
+        }
+    }
+
+    pub fn columns(&self) -> [&Column; 4] {
+        [&self.r#type, &self.client, &self.tx, &self.amount]
+    }
+}
+
+/// Describes the layout of a partner CSV, typically loaded from JSON:
+///
+/// ```json
+/// { "has_headers": false, "delimiter": ";", "columns": { "type": 2, "client": 0, "tx": 1, "amount": 3 } }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct CsvSchema {
+    pub has_headers: bool,
+    pub delimiter: char,
+    /// Defaults to the standard header names, or to positions 0-3 without headers.
+    pub columns: Option<ColumnMapping>,
+}
+
+impl Default for CsvSchema {
+    fn default() -> Self {
+        Self {
+            has_headers: true,
+            delimiter: ',',
+            columns: None,
+        }
+    }
+}
+
+impl CsvSchema {
+    pub fn mapping(&self) -> ColumnMapping {
+        match &self.columns {
+            Some(columns) => columns.clone(),
+            None if self.has_headers => ColumnMapping::by_name(),
+            None => ColumnMapping::by_index(),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! HTTP API around a single engine, owned by one writer task.
+
+use axum::{
+    Json, Router,
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::Serialize;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, oneshot},
+};
+
+use crate::{
+    file_reader::JsonRecord,
+    ledger::engine::PaymentsEngine,
+    model::{ClientId, RawRecord, TxId},
+    output::{OutputRecord, output_records},
+    transaction::{Transaction, TransactionError},
+    validation::check_strict,
+};
+
+/// Requests queued ahead of the writer task before senders wait.
+const QUEUE_DEPTH: usize = 1024;
+
+enum Command {
+    Apply(Vec<RawRecord>, oneshot::Sender<Vec<Outcome>>),
+    Account(ClientId, oneshot::Sender<Option<OutputRecord>>),
+    Accounts(oneshot::Sender<Vec<OutputRecord>>),
+    Transaction(TxId, oneshot::Sender<Option<Transaction>>),
+}
+
+/// What happened to one submitted record.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum Outcome {
+    Applied { tx: TxId },
+    Rejected { tx: TxId, error: TransactionError },
+}
+
+fn run(engine: &mut PaymentsEngine, command: Command) {
+    // A dropped receiver only means the client went away, so reply errors are ignored
+    match command {
+        Command::Apply(records, reply) => {
+            let outcomes = records
+                .into_iter()
+                .map(|record| {
+                    let tx = record.tx;
+                    match check_strict(record).and_then(|record| engine.process_csv_record(record))
+                    {
+                        Ok(()) => Outcome::Applied { tx },
+                        Err(error) => Outcome::Rejected { tx, error },
+                    }
+                })
+                .collect();
+            let _ = reply.send(outcomes);
+        }
+        Command::Account(client, reply) => {
+            let account = output_records(&engine.client_manager, engine.precision())
+                .find(|account| account.client == client);
+            let _ = reply.send(account);
+        }
+        Command::Accounts(reply) => {
+            let accounts = output_records(&engine.client_manager, engine.precision()).collect();
+            let _ = reply.send(accounts);
+        }
+        Command::Transaction(tx, reply) => {
+            let _ = reply.send(engine.tx_manager.get(tx).cloned());
+        }
+    }
+}
+
+/// Cloneable handle to the writer task that owns the engine.
+#[derive(Clone)]
+pub struct EngineHandle {
+    commands: mpsc::Sender<Command>,
+}
+
+impl EngineHandle {
+    /// Moves `engine` into a new writer task. Must be called within a Tokio runtime.
+    pub fn spawn(mut engine: PaymentsEngine) -> Self {
+        let (commands, mut rx) = mpsc::channel(QUEUE_DEPTH);
+        tokio::spawn(async move {
+            while let Some(command) = rx.recv().await {
+                run(&mut engine, command);
+            }
+        });
+        Self { commands }
+    }
+
+    /// `None` once the writer task has stopped.
+    async fn request<T>(&self, command: impl FnOnce(oneshot::Sender<T>) -> Command) -> Option<T> {
+        let (reply, rx) = oneshot::channel();
+        self.commands.send(command(reply)).await.ok()?;
+        rx.await.ok()
+    }
+
+    /// Validates and applies `records` in order, with one outcome per record.
+    pub async fn apply(&self, records: Vec<RawRecord>) -> Option<Vec<Outcome>> {
+        self.request(|reply| Command::Apply(records, reply)).await
+    }
+
+    pub async fn account(&self, client: ClientId) -> Option<Option<OutputRecord>> {
+        self.request(|reply| Command::Account(client, reply)).await
+    }
+
+    pub async fn accounts(&self) -> Option<Vec<OutputRecord>> {
+        self.request(Command::Accounts).await
+    }
+
+    pub async fn transaction(&self, tx: TxId) -> Option<Option<Transaction>> {
+        self.request(|reply| Command::Transaction(tx, reply)).await
+    }
+}
+
+const STOPPED: StatusCode = StatusCode::SERVICE_UNAVAILABLE;
+
+/// `POST /transactions` takes either one record or an array of them.
+enum Submission {
+    One(JsonRecord),
+    Batch(Vec<JsonRecord>),
+}
+
+impl Submission {
+    /// Dispatches on the leading bracket rather than using an untagged enum, which would buffer
+    /// the amounts and lose their exact text.
+    fn parse(body: &[u8]) -> serde_json::Result<Self> {
+        match body.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(b'[') => serde_json::from_slice(body).map(Submission::Batch),
+            _ => serde_json::from_slice(body).map(Submission::One),
+        }
+    }
+}
+
+async fn post_transactions(
+    State(handle): State<EngineHandle>,
+    body: Bytes,
+) -> Result<Response, StatusCode> {
+    let submission = match Submission::parse(&body) {
+        Ok(submission) => submission,
+        Err(err) => return Ok((StatusCode::BAD_REQUEST, err.to_string()).into_response()),
+    };
+
+    Ok(match submission {
+        Submission::One(record) => {
+            let mut outcomes = handle.apply(vec![record.into()]).await.ok_or(STOPPED)?;
+            let outcome = outcomes.pop().ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+            let status = match outcome {
+                Outcome::Applied { .. } => StatusCode::OK,
+                Outcome::Rejected { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            (status, Json(outcome)).into_response()
+        }
+        Submission::Batch(records) => {
+            let records = records.into_iter().map(RawRecord::from).collect();
+            let outcomes = handle.apply(records).await.ok_or(STOPPED)?;
+            Json(outcomes).into_response()
+        }
+    })
+}
+
+async fn get_account(
+    State(handle): State<EngineHandle>,
+    Path(client): Path<u16>,
+) -> Result<Json<OutputRecord>, StatusCode> {
+    handle
+        .account(client.into())
+        .await
+        .ok_or(STOPPED)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn get_accounts(
+    State(handle): State<EngineHandle>,
+) -> Result<Json<Vec<OutputRecord>>, StatusCode> {
+    handle.accounts().await.map(Json).ok_or(STOPPED)
+}
+
+async fn get_transaction(
+    State(handle): State<EngineHandle>,
+    Path(tx): Path<u32>,
+) -> Result<Json<Transaction>, StatusCode> {
+    handle
+        .transaction(tx.into())
+        .await
+        .ok_or(STOPPED)?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+pub fn router(handle: EngineHandle) -> Router {
+    Router::new()
+        .route("/transactions", post(post_transactions))
+        .route("/transactions/{tx}", get(get_transaction))
+        .route("/accounts", get(get_accounts))
+        .route("/accounts/{client}", get(get_account))
+        .with_state(handle)
+}
+
+/// Serves the API on `listener` until the process exits.
+pub async fn serve(listener: TcpListener, handle: EngineHandle) -> std::io::Result<()> {
+    axum::serve(listener, router(handle)).await
+}
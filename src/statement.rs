@@ -0,0 +1,263 @@
+//! Per-client statements rebuilt from the journal.
+
+use std::io;
+
+use bigdecimal::{BigDecimal, num_traits::zero};
+use serde::{Serialize, Serializer};
+
+use crate::{
+    events::Balances,
+    ledger::journal::{Journal, JournalEntry, LedgerAccount},
+    model::{ClientId, TxId, TxType},
+    output::serialise_decimal,
+    precision::PrecisionPolicy,
+};
+
+/// One posting to the client's account, with the balances after it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementEntry {
+    pub position: u64,
+    pub tx: TxId,
+    pub operation: TxType,
+    pub amount: BigDecimal,
+    pub after: Balances,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub client: ClientId,
+    pub from: u64,
+    pub to: u64,
+    pub opening: Balances,
+    pub entries: Vec<StatementEntry>,
+    pub closing: Balances,
+}
+
+/// The operation a posting records for `client` and how it moves the available and held
+/// balances, or `None` if it does not touch the client.
+fn classify(entry: &JournalEntry, client: ClientId) -> Option<(TxType, BigDecimal, BigDecimal)> {
+    use LedgerAccount::{ChargebackLoss, ClientAvailable, ClientHeld, Settlement};
+
+    let amount = entry.amount.clone();
+    match (entry.debit, entry.credit) {
+        (Settlement, ClientAvailable(c)) if c == client => Some((TxType::Deposit, amount, zero())),
+        (ClientAvailable(c), Settlement) if c == client => {
+            Some((TxType::Withdrawal, -amount, zero()))
+        }
+        (ClientAvailable(c), ClientHeld(_)) if c == client => {
+            Some((TxType::Dispute, -amount.clone(), amount))
+        }
+        (ClientHeld(c), ClientAvailable(_)) if c == client => {
+            Some((TxType::Resolve, amount.clone(), -amount))
+        }
+        (ClientHeld(c), ChargebackLoss) if c == client => {
+            Some((TxType::Chargeback, zero(), -amount))
+        }
+        _ => None,
+    }
+}
+
+fn apply(balances: &mut Balances, operation: &TxType, available: &BigDecimal, held: &BigDecimal) {
+    balances.available += available;
+    balances.held += held;
+    balances.total = &balances.available + &balances.held;
+    balances.locked |= *operation == TxType::Chargeback;
+}
+
+impl Statement {
+    /// `client`'s postings made by input records `from..=to`, in the order they were applied.
+    pub fn build(journal: &Journal, client: ClientId, from: u64, to: u64) -> Self {
+        let mut opening = Balances::default();
+        let mut closing = Balances::default();
+        let mut entries = Vec::new();
+
+        for entry in journal.entries() {
+            let Some((operation, available, held)) = classify(entry, client) else {
+                continue;
+            };
+            if entry.position < from {
+                apply(&mut opening, &operation, &available, &held);
+            }
+            if entry.position <= to {
+                apply(&mut closing, &operation, &available, &held);
+            }
+            if (from..=to).contains(&entry.position) {
+                entries.push(StatementEntry {
+                    position: entry.position,
+                    tx: entry.tx,
+                    amount: entry.amount.clone(),
+                    operation,
+                    after: closing.clone(),
+                });
+            }
+        }
+
+        Self {
+            client,
+            from,
+            to,
+            opening,
+            entries,
+            closing,
+        }
+    }
+
+    /// The statement as flat rows, between an `opening` and a `closing` row, with amounts
+    /// rounded to the `display` precision.
+    pub fn rows(&self, display: &PrecisionPolicy) -> Vec<StatementRow> {
+        let row = |record, tx, operation, amount: Option<&BigDecimal>, balances: &Balances| {
+            StatementRow {
+                client: self.client,
+                record,
+                tx,
+                operation,
+                amount: amount.map(|amount| display.round(amount)),
+                available: display.round(&balances.available),
+                held: display.round(&balances.held),
+                total: display.round(&balances.total),
+                locked: balances.locked,
+            }
+        };
+
+        let mut rows = vec![row(None, None, "opening", None, &self.opening)];
+        rows.extend(self.entries.iter().map(|entry| {
+            row(
+                Some(entry.position),
+                Some(entry.tx),
+                entry.operation.name(),
+                Some(&entry.amount),
+                &entry.after,
+            )
+        }));
+        rows.push(row(None, None, "closing", None, &self.closing));
+        rows
+    }
+
+    /// The statement as a plain-text table.
+    pub fn write_text<W: io::Write>(
+        &self,
+        display: &PrecisionPolicy,
+        mut wtr: W,
+    ) -> io::Result<()> {
+        writeln!(
+            wtr,
+            "Statement for client {}, records {} to {}",
+            self.client, self.from, self.to
+        )?;
+        writeln!(wtr)?;
+        writeln!(
+            wtr,
+            "{:>8}  {:>10}  {:<10}  {:>14}  {:>14}  {:>14}  {:>14}",
+            "record", "tx", "operation", "amount", "available", "held", "total"
+        )?;
+        for row in self.rows(display) {
+            let text = |value: Option<String>| value.unwrap_or_default();
+            writeln!(
+                wtr,
+                "{:>8}  {:>10}  {:<10}  {:>14}  {:>14}  {:>14}  {:>14}",
+                text(row.record.map(|record| record.to_string())),
+                text(row.tx.map(|tx| tx.to_string())),
+                row.operation,
+                text(row.amount.map(|amount| amount.to_string())),
+                row.available,
+                row.held,
+                row.total,
+            )?;
+        }
+        if self.closing.locked {
+            writeln!(wtr)?;
+            writeln!(wtr, "The account is locked.")?;
+        }
+        wtr.flush()
+    }
+}
+
+fn serialise_optional_decimal<S>(
+    decimal: &Option<BigDecimal>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match decimal {
+        Some(decimal) => serialise_decimal(decimal, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// A statement line as written in CSV or JSON.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct StatementRow {
+    pub client: ClientId,
+    /// The input record that made the posting.
+    pub record: Option<u64>,
+    pub tx: Option<TxId>,
+    pub operation: &'static str,
+    #[serde(serialize_with = "serialise_optional_decimal")]
+    pub amount: Option<BigDecimal>,
+    #[serde(serialize_with = "serialise_decimal")]
+    pub available: BigDecimal,
+    #[serde(serialize_with = "serialise_decimal")]
+    pub held: BigDecimal,
+    #[serde(serialize_with = "serialise_decimal")]
+    pub total: BigDecimal,
+    pub locked: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        file_reader::csv_stream, ledger::engine::PaymentsEngine, model::TxType,
+        precision::PrecisionPolicy, statement::Statement,
+    };
+
+    #[test]
+    fn test_statement_over_a_record_range() {
+        let test_data = r#"type,client,tx,amount
+deposit,1,1,10.0
+deposit,2,2,5.0
+withdrawal,1,3,4.0
+dispute,1,1,
+withdrawal,1,4,50.0
+chargeback,1,1,
+"#;
+        let mut payment_engine = PaymentsEngine::default();
+        for record in csv_stream(test_data.as_bytes()) {
+            let _ = payment_engine.process_csv_record(record.unwrap());
+        }
+
+        let statement = Statement::build(&payment_engine.journal, 1.into(), 3, 6);
+        assert_eq!(statement.opening.available, "10".parse().unwrap());
+        let operations = statement
+            .entries
+            .iter()
+            .map(|entry| (entry.position, entry.operation.clone()))
+            .collect::<Vec<_>>();
+        // The rejected withdrawal posts nothing, and client 2's deposit is someone else's
+        assert_eq!(
+            operations,
+            [
+                (3, TxType::Withdrawal),
+                (4, TxType::Dispute),
+                (6, TxType::Chargeback)
+            ]
+        );
+        assert_eq!(statement.entries[1].after.held, "10".parse().unwrap());
+        assert_eq!(statement.closing.total, "-4".parse().unwrap());
+        assert!(statement.closing.locked);
+
+        let rows = statement.rows(&PrecisionPolicy::default());
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0].operation, "opening");
+        assert_eq!(rows[4].operation, "closing");
+        assert_eq!(rows[4].total.to_string(), "-4.0000");
+
+        let mut text = Vec::new();
+        statement
+            .write_text(&PrecisionPolicy::default(), &mut text)
+            .unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.starts_with("Statement for client 1, records 3 to 6"));
+        assert!(text.ends_with("The account is locked.\n"));
+    }
+}
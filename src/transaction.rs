@@ -1,30 +1,135 @@
 use bigdecimal::{BigDecimal, num_traits::zero};
+use serde::{Deserialize, Serialize};
 
-use crate::model::{CSVRecord, ClientId, TxId, TxType};
+use crate::{
+    model::{CSVRecord, ClientId, TxId, TxType},
+    output::{deserialise_decimal, serialise_decimal},
+};
 
 pub type TxResult = Result<(), TransactionError>;
 
-#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+/// Why a record was rejected. Every variant names the client and tx it concerns, and serialises
+/// with its stable [`code`](TransactionError::code) under a `code` tag so rejections can be
+/// aggregated downstream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[serde(tag = "code", rename_all = "snake_case")]
 pub enum TransactionError {
-    #[error("client ID is invalid")]
-    InvalidClinetId,
-    #[error("account has insufficient funds")]
-    InsufficientFunds,
-    #[error("account locked")]
-    AccountLocked,
-    #[error("missing amount")]
-    MissingAmount,
-    #[error("invalid amount")]
-    InvalidAmount,
-    #[error("{0:?} is not a storable transaction")]
-    NotStorable(TxType),
-    #[error("attempted operation on TxId={0} was not possible as no existing record exists")]
-    MissingTransaction(TxId),
-    #[error("duplicate transaction")]
-    DuplicateTransactionId(TxId),
+    #[error("client {actual}, tx {tx}: transaction belongs to client {expected}")]
+    ClientMismatch {
+        tx: TxId,
+        expected: ClientId,
+        actual: ClientId,
+    },
+    #[error(
+        "client {client}, tx {tx}: insufficient funds, requested {requested} but {available} available"
+    )]
+    InsufficientFunds {
+        client: ClientId,
+        tx: TxId,
+        #[serde(serialize_with = "serialise_decimal")]
+        requested: BigDecimal,
+        #[serde(serialize_with = "serialise_decimal")]
+        available: BigDecimal,
+    },
+    #[error("client {client}, tx {tx}: account locked")]
+    AccountLocked { client: ClientId, tx: TxId },
+    #[error("client {client}, tx {tx}: {} requires an amount", r#type.name())]
+    MissingAmount {
+        client: ClientId,
+        tx: TxId,
+        r#type: TxType,
+    },
+    #[error("client {client}, tx {tx}: invalid amount {amount}")]
+    InvalidAmount {
+        client: ClientId,
+        tx: TxId,
+        #[serde(serialize_with = "serialise_decimal")]
+        amount: BigDecimal,
+    },
+    #[error("client {client}, tx {tx}: {} is not a storable transaction", r#type.name())]
+    NotStorable {
+        client: ClientId,
+        tx: TxId,
+        r#type: TxType,
+    },
+    #[error("client {client}, tx {tx}: no existing transaction")]
+    MissingTransaction { client: ClientId, tx: TxId },
+    #[error("client {client}, tx {tx}: duplicate transaction")]
+    DuplicateTransactionId { client: ClientId, tx: TxId },
+    #[error("client {client}, tx {tx}: amount {amount} has more than {scale} decimal places")]
+    ExcessPrecision {
+        client: ClientId,
+        tx: TxId,
+        #[serde(serialize_with = "serialise_decimal")]
+        amount: BigDecimal,
+        scale: i64,
+    },
+    #[error("client {client}, tx {tx}: {} must not carry an amount, got `{amount}`", r#type.name())]
+    UnexpectedAmount {
+        client: ClientId,
+        tx: TxId,
+        r#type: TxType,
+        amount: String,
+    },
+    #[error("client {client}, tx {tx}: amount `{amount}` is not a plain decimal number")]
+    MalformedAmount {
+        client: ClientId,
+        tx: TxId,
+        amount: String,
+    },
+    #[error("client {client}, tx {tx}: amount `{amount}` uses exponent notation")]
+    ExponentNotation {
+        client: ClientId,
+        tx: TxId,
+        amount: String,
+    },
+    #[error("client {client}, tx {tx}: amount `{amount}` is negative")]
+    NegativeAmount {
+        client: ClientId,
+        tx: TxId,
+        amount: String,
+    },
+    #[error("client {client}, tx {tx}: amount `{amount}` is zero once rounded")]
+    ZeroAmount {
+        client: ClientId,
+        tx: TxId,
+        amount: String,
+    },
+}
+
+impl TransactionError {
+    /// Stable identifier for the kind of rejection, matching the serialised `code` tag.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TransactionError::ClientMismatch { .. } => "client_mismatch",
+            TransactionError::InsufficientFunds { .. } => "insufficient_funds",
+            TransactionError::AccountLocked { .. } => "account_locked",
+            TransactionError::MissingAmount { .. } => "missing_amount",
+            TransactionError::InvalidAmount { .. } => "invalid_amount",
+            TransactionError::NotStorable { .. } => "not_storable",
+            TransactionError::MissingTransaction { .. } => "missing_transaction",
+            TransactionError::DuplicateTransactionId { .. } => "duplicate_transaction_id",
+            TransactionError::ExcessPrecision { .. } => "excess_precision",
+            TransactionError::UnexpectedAmount { .. } => "unexpected_amount",
+            TransactionError::MalformedAmount { .. } => "malformed_amount",
+            TransactionError::ExponentNotation { .. } => "exponent_notation",
+            TransactionError::NegativeAmount { .. } => "negative_amount",
+            TransactionError::ZeroAmount { .. } => "zero_amount",
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// The record that failed an atomic batch, given by its 0-based `index` in the batch. Nothing
+/// from the batch was applied.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("batch rolled back at record {index}: {error}")]
+pub struct BatchError {
+    pub index: usize,
+    pub error: TransactionError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum TransactionStatus {
     Processed,
     Disputed,
@@ -32,11 +137,15 @@ pub enum TransactionStatus {
     Chargedback,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub tx: TxId,
     pub client: ClientId,
     pub r#type: TxType,
+    #[serde(
+        serialize_with = "serialise_decimal",
+        deserialize_with = "deserialise_decimal"
+    )]
     pub amount: BigDecimal,
     pub status: TransactionStatus,
 }
@@ -64,7 +173,13 @@ impl TryFrom<CSVRecord> for Transaction {
     fn try_from(value: CSVRecord) -> Result<Self, Self::Error> {
         match value.r#type {
             TxType::Deposit | TxType::Withdrawal => match value.amount {
-                Some(amount) if amount < zero() => Err(TransactionError::InvalidAmount),
+                Some(amount) if amount < zero::<BigDecimal>() => {
+                    Err(TransactionError::InvalidAmount {
+                        client: value.client,
+                        tx: value.tx,
+                        amount,
+                    })
+                }
                 Some(amount) => Ok(Transaction {
                     tx: value.tx,
                     client: value.client,
@@ -72,9 +187,54 @@ impl TryFrom<CSVRecord> for Transaction {
                     status: TransactionStatus::Processed,
                     r#type: value.r#type,
                 }),
-                None => Err(TransactionError::MissingAmount),
+                None => Err(TransactionError::MissingAmount {
+                    client: value.client,
+                    tx: value.tx,
+                    r#type: value.r#type,
+                }),
             },
-            _ => Err(TransactionError::NotStorable(value.r#type)),
+            _ => Err(TransactionError::NotStorable {
+                client: value.client,
+                tx: value.tx,
+                r#type: value.r#type,
+            }),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::transaction::TransactionError;
+
+    #[test]
+    fn test_error_serialises_code_and_context() {
+        let err = TransactionError::InsufficientFunds {
+            client: 1.into(),
+            tx: 2.into(),
+            requested: "5.0000".parse().unwrap(),
+            available: "1.5".parse().unwrap(),
+        };
+
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            serde_json::json!({
+                "code": "insufficient_funds",
+                "client": 1,
+                "tx": 2,
+                "requested": "5.0000",
+                "available": "1.5",
+            })
+        );
+        assert_eq!(
+            err.to_string(),
+            "client 1, tx 2: insufficient funds, requested 5.0000 but 1.5 available"
+        );
+
+        let err = TransactionError::ClientMismatch {
+            tx: 3.into(),
+            expected: 1.into(),
+            actual: 2.into(),
+        };
+        assert_eq!(serde_json::to_value(&err).unwrap()["code"], err.code());
+    }
+}
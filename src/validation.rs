@@ -0,0 +1,233 @@
+use std::collections::BTreeSet;
+
+use bigdecimal::{BigDecimal, Zero as _, num_traits::zero};
+
+use crate::{
+    model::{CSVRecord, RawRecord, TxId, TxType, parse_decimal},
+    transaction::{TransactionError, TxResult},
+};
+
+/// Digits with an optional sign and decimal point, e.g. `12`, `-0.5`, `.25` or `3.`.
+fn is_plain_decimal(amount: &str) -> bool {
+    let unsigned = amount.strip_prefix(['+', '-']).unwrap_or(amount);
+    let (whole, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    !(whole.is_empty() && fraction.is_empty())
+        && whole.bytes().all(|b| b.is_ascii_digit())
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Enforces the per-type shape of a record before it reaches the engine: deposits and
+/// withdrawals need a positive amount written as a plain decimal, while disputes, resolves and
+/// chargebacks must not carry one.
+pub fn check_strict(record: RawRecord) -> Result<CSVRecord, TransactionError> {
+    let amount = record
+        .amount
+        .as_deref()
+        .map(str::trim)
+        .filter(|amount| !amount.is_empty());
+
+    let amount = match (&record.r#type, amount) {
+        (TxType::Deposit | TxType::Withdrawal, None) => {
+            return Err(TransactionError::MissingAmount {
+                client: record.client,
+                tx: record.tx,
+                r#type: record.r#type,
+            });
+        }
+        (TxType::Deposit | TxType::Withdrawal, Some(amount)) => amount,
+        (TxType::Dispute | TxType::Resolve | TxType::Chargeback, None) => {
+            return Ok(CSVRecord {
+                r#type: record.r#type,
+                client: record.client,
+                tx: record.tx,
+                amount: None,
+            });
+        }
+        (r#type, Some(amount)) => {
+            return Err(TransactionError::UnexpectedAmount {
+                client: record.client,
+                tx: record.tx,
+                r#type: r#type.clone(),
+                amount: amount.to_owned(),
+            });
+        }
+    };
+
+    let (client, tx, text) = (record.client, record.tx, amount.to_owned());
+    let parsed = parse_decimal(amount).ok().flatten();
+    let parsed = match parsed {
+        Some(parsed) if is_plain_decimal(amount) => parsed,
+        Some(_) if amount.contains(['e', 'E']) => {
+            return Err(TransactionError::ExponentNotation {
+                client,
+                tx,
+                amount: text,
+            });
+        }
+        _ => {
+            return Err(TransactionError::MalformedAmount {
+                client,
+                tx,
+                amount: text,
+            });
+        }
+    };
+    if parsed.is_zero() {
+        return Err(TransactionError::ZeroAmount {
+            client,
+            tx,
+            amount: text,
+        });
+    }
+    if parsed < zero::<BigDecimal>() {
+        return Err(TransactionError::NegativeAmount {
+            client,
+            tx,
+            amount: text,
+        });
+    }
+
+    Ok(CSVRecord {
+        r#type: record.r#type,
+        client: record.client,
+        tx: record.tx,
+        amount: Some(parsed),
+    })
+}
+
+/// Checks records for problems that can be found without applying them to an engine.
+#[derive(Default)]
+pub struct Validator {
+    seen: BTreeSet<TxId>,
+}
+
+impl Validator {
+    pub fn check(&mut self, record: &CSVRecord) -> TxResult {
+        match record.r#type {
+            TxType::Deposit | TxType::Withdrawal => {
+                if !self.seen.insert(record.tx) {
+                    return Err(TransactionError::DuplicateTransactionId {
+                        client: record.client,
+                        tx: record.tx,
+                    });
+                }
+                match &record.amount {
+                    None => Err(TransactionError::MissingAmount {
+                        client: record.client,
+                        tx: record.tx,
+                        r#type: record.r#type.clone(),
+                    }),
+                    Some(amount) if amount < &zero::<BigDecimal>() => {
+                        Err(TransactionError::InvalidAmount {
+                            client: record.client,
+                            tx: record.tx,
+                            amount: amount.clone(),
+                        })
+                    }
+                    Some(_) => Ok(()),
+                }
+            }
+            TxType::Dispute | TxType::Resolve | TxType::Chargeback => {
+                if self.seen.contains(&record.tx) {
+                    Ok(())
+                } else {
+                    Err(TransactionError::MissingTransaction {
+                        client: record.client,
+                        tx: record.tx,
+                    })
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        file_reader::{csv_stream, raw_csv_stream},
+        transaction::TransactionError,
+        validation::{Validator, check_strict},
+    };
+
+    #[test]
+    fn test_strict_rules_per_type() {
+        let test_data = r#"type,client,tx,amount
+deposit,1,1,100.0
+withdrawal,1,2,
+dispute,1,1,5.0
+resolve,1,1,
+deposit,1,3,0.000
+withdrawal,1,4,-1.5
+deposit,1,5,1e5
+deposit,1,6,1.2.3
+chargeback,1,1, 
+deposit,1,7,.5
+"#;
+
+        let results = raw_csv_stream(test_data.as_bytes())
+            .map(|record| {
+                check_strict(record.unwrap())
+                    .map(|record| record.amount)
+                    .map_err(|err| err.code())
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(Some("100.0".parse().unwrap())),
+                Err("missing_amount"),
+                Err("unexpected_amount"),
+                Ok(None),
+                Err("zero_amount"),
+                Err("negative_amount"),
+                Err("exponent_notation"),
+                Err("malformed_amount"),
+                Ok(None),
+                Ok(Some("0.5".parse().unwrap())),
+            ]
+        );
+
+        let record = raw_csv_stream("type,client,tx,amount\ndeposit,3,9,1E5\n".as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            check_strict(record).unwrap_err(),
+            TransactionError::ExponentNotation {
+                client: 3.into(),
+                tx: 9.into(),
+                amount: "1E5".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_validator_reports_without_applying() {
+        let test_data = r#" type,  client,  tx,  amount
+deposit,1,1,100.0
+deposit,1,1,100.0
+withdrawal,1,2,
+deposit,1,3,-1.0
+dispute,1,4,
+withdrawal,1,5,500.0
+"#;
+
+        let mut validator = Validator::default();
+        let results = csv_stream(test_data.as_bytes())
+            .map(|record| validator.check(&record.unwrap()).map_err(|err| err.code()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Err("duplicate_transaction_id"),
+                Err("missing_amount"),
+                Err("invalid_amount"),
+                Err("missing_transaction"),
+                Ok(()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,488 @@
+use std::{collections::BTreeMap, io};
+
+use bigdecimal::{BigDecimal, ToPrimitive as _};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{
+    model::{CSVRecord, ClientId, TxId, TxType},
+    output::OutputRecord,
+    transaction::TransactionStatus,
+};
+
+/// Amounts are generated as whole units of the smallest supported fraction (4 dp).
+const SCALE: i64 = 4;
+const MAX_DEPOSIT_UNITS: i64 = 100_000_000;
+
+#[derive(Debug, Clone)]
+pub struct WorkloadConfig {
+    pub clients: u16,
+    pub transactions: u32,
+    /// Share of deposit/withdrawal records that are withdrawals.
+    pub withdrawal_ratio: f64,
+    pub dispute_ratio: f64,
+    pub resolve_ratio: f64,
+    pub chargeback_ratio: f64,
+    /// Share of records deliberately crafted to be rejected by the engine.
+    pub error_rate: f64,
+    pub seed: u64,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            clients: 100,
+            transactions: 10_000,
+            withdrawal_ratio: 0.3,
+            dispute_ratio: 0.05,
+            resolve_ratio: 0.03,
+            chargeback_ratio: 0.01,
+            error_rate: 0.01,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum WorkloadError {
+    #[error("{name} must be between 0 and 1, got {value}")]
+    RatioOutOfRange { name: &'static str, value: f64 },
+    #[error("dispute, resolve and chargeback ratios sum to {0}, which is more than 1")]
+    DisputeRatiosExceedOne(f64),
+}
+
+impl WorkloadConfig {
+    /// Every ratio is a probability, and the dispute, resolve and chargeback ratios share one
+    /// roll, so together they cannot exceed 1.
+    pub fn validate(&self) -> Result<(), WorkloadError> {
+        for (name, value) in [
+            ("withdrawal_ratio", self.withdrawal_ratio),
+            ("dispute_ratio", self.dispute_ratio),
+            ("resolve_ratio", self.resolve_ratio),
+            ("chargeback_ratio", self.chargeback_ratio),
+            ("error_rate", self.error_rate),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(WorkloadError::RatioOutOfRange { name, value });
+            }
+        }
+
+        let disputes = self.dispute_ratio + self.resolve_ratio + self.chargeback_ratio;
+        if disputes > 1.0 {
+            return Err(WorkloadError::DisputeRatiosExceedOne(disputes));
+        }
+        Ok(())
+    }
+}
+
+pub struct Workload {
+    pub records: Vec<CSVRecord>,
+    /// Final account states, computed independently of `PaymentsEngine`.
+    pub expected: Vec<OutputRecord>,
+}
+
+impl Workload {
+    /// Panics if `config` does not pass [`WorkloadConfig::validate`].
+    pub fn generate(config: &WorkloadConfig) -> Self {
+        let mut generator = Generator {
+            rng: StdRng::seed_from_u64(config.seed),
+            config: config.clone(),
+            oracle: Oracle::default(),
+            records: Vec::with_capacity(config.transactions as usize),
+            disputable: Vec::new(),
+            disputed: Vec::new(),
+            next_tx: 1,
+        };
+
+        for _ in 0..config.transactions {
+            generator.step();
+        }
+
+        Self {
+            records: generator.records,
+            expected: generator.oracle.output_records(),
+        }
+    }
+
+    pub fn write_csv<W: io::Write>(&self, wtr: W) -> Result<(), csv::Error> {
+        write_all(wtr, &self.records)
+    }
+
+    pub fn write_expected<W: io::Write>(&self, wtr: W) -> Result<(), csv::Error> {
+        write_all(wtr, &self.expected)
+    }
+}
+
+fn write_all<W: io::Write, T: serde::Serialize>(wtr: W, rows: &[T]) -> Result<(), csv::Error> {
+    let mut csv_wtr = csv::WriterBuilder::new().has_headers(true).from_writer(wtr);
+    for row in rows {
+        csv_wtr.serialize(row)?;
+    }
+    csv_wtr.flush()?;
+    Ok(())
+}
+
+fn to_decimal(units: i64) -> BigDecimal {
+    BigDecimal::new(units.into(), SCALE)
+}
+
+fn to_units(amount: &BigDecimal) -> Option<i64> {
+    amount
+        .with_scale(SCALE)
+        .into_bigint_and_exponent()
+        .0
+        .to_i64()
+}
+
+struct Generator {
+    rng: StdRng,
+    config: WorkloadConfig,
+    oracle: Oracle,
+    records: Vec<CSVRecord>,
+    /// Deposits that are currently `Processed` or `Resolved`.
+    disputable: Vec<TxId>,
+    disputed: Vec<TxId>,
+    next_tx: u32,
+}
+
+impl Generator {
+    fn step(&mut self) {
+        if self.rng.random_bool(self.config.error_rate) {
+            return self.push_error();
+        }
+
+        let roll = self.rng.random::<f64>();
+        let dispute = self.config.dispute_ratio;
+        let resolve = dispute + self.config.resolve_ratio;
+        let chargeback = resolve + self.config.chargeback_ratio;
+
+        let applied = if roll < dispute {
+            self.push_dispute()
+        } else if roll < resolve {
+            self.push_dispute_outcome(TxType::Resolve)
+        } else if roll < chargeback {
+            self.push_dispute_outcome(TxType::Chargeback)
+        } else {
+            false
+        };
+
+        if !applied {
+            self.push_deposit_or_withdrawal();
+        }
+    }
+
+    fn push(&mut self, r#type: TxType, client: ClientId, tx: TxId, amount: Option<i64>) {
+        let record = CSVRecord {
+            r#type,
+            client,
+            tx,
+            amount: amount.map(to_decimal),
+        };
+        self.oracle.apply(&record);
+        self.records.push(record);
+    }
+
+    fn new_tx(&mut self) -> TxId {
+        let tx = TxId(self.next_tx);
+        self.next_tx += 1;
+        tx
+    }
+
+    fn random_client(&mut self) -> ClientId {
+        ClientId(self.rng.random_range(1..=self.config.clients.max(1)))
+    }
+
+    fn push_deposit_or_withdrawal(&mut self) {
+        let client = self.random_client();
+        let available = self.oracle.available(client);
+        let tx = self.new_tx();
+
+        if available > 0 && self.rng.random_bool(self.config.withdrawal_ratio) {
+            let amount = self.rng.random_range(1..=available);
+            self.push(TxType::Withdrawal, client, tx, Some(amount));
+        } else {
+            let amount = self.rng.random_range(1..=MAX_DEPOSIT_UNITS);
+            self.push(TxType::Deposit, client, tx, Some(amount));
+            if self.oracle.is_stored(tx) {
+                self.disputable.push(tx);
+            }
+        }
+    }
+
+    fn push_dispute(&mut self) -> bool {
+        if self.disputable.is_empty() {
+            return false;
+        }
+        let idx = self.rng.random_range(0..self.disputable.len());
+        let tx = self.disputable.swap_remove(idx);
+        let client = self.oracle.client_of(tx);
+
+        self.push(TxType::Dispute, client, tx, None);
+        self.disputed.push(tx);
+        true
+    }
+
+    fn push_dispute_outcome(&mut self, r#type: TxType) -> bool {
+        if self.disputed.is_empty() {
+            return false;
+        }
+        let idx = self.rng.random_range(0..self.disputed.len());
+        let tx = self.disputed.swap_remove(idx);
+        let client = self.oracle.client_of(tx);
+
+        if r#type == TxType::Resolve {
+            self.disputable.push(tx);
+        }
+        self.push(r#type, client, tx, None);
+        true
+    }
+
+    fn push_error(&mut self) {
+        match self.rng.random_range(0..5) {
+            // Withdrawal exceeding the available balance
+            0 => {
+                let client = self.random_client();
+                let amount = self.oracle.available(client).max(0) + 1;
+                let tx = self.new_tx();
+                self.push(TxType::Withdrawal, client, tx, Some(amount));
+            }
+            // Duplicate transaction ID
+            1 if self.next_tx > 1 => {
+                let client = self.random_client();
+                let tx = TxId(self.rng.random_range(1..self.next_tx));
+                self.push(TxType::Deposit, client, tx, Some(1));
+            }
+            // Dispute raised by a client that does not own the transaction
+            2 if self.config.clients > 1 && !self.disputable.is_empty() => {
+                let idx = self.rng.random_range(0..self.disputable.len());
+                let tx = self.disputable[idx];
+                let owner = self.oracle.client_of(tx);
+                let client = ClientId(owner.0 % self.config.clients + 1);
+                self.push(TxType::Dispute, client, tx, None);
+            }
+            // Negative amount
+            3 => {
+                let client = self.random_client();
+                let tx = self.new_tx();
+                let amount = -self.rng.random_range(1..=MAX_DEPOSIT_UNITS);
+                self.push(TxType::Deposit, client, tx, Some(amount));
+            }
+            // Dispute of a transaction that never existed
+            _ => {
+                let client = self.random_client();
+                let tx = TxId(u32::MAX - self.records.len() as u32);
+                self.push(TxType::Dispute, client, tx, None);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct OracleAccount {
+    available: i64,
+    held: i64,
+    locked: bool,
+}
+
+struct OracleTransaction {
+    client: ClientId,
+    r#type: TxType,
+    amount: i64,
+    status: TransactionStatus,
+}
+
+/// Reference model of the engine's rules, kept deliberately separate from `PaymentsEngine`
+/// so the expected output can act as an independent oracle.
+#[derive(Default)]
+struct Oracle {
+    accounts: BTreeMap<ClientId, OracleAccount>,
+    transactions: BTreeMap<TxId, OracleTransaction>,
+}
+
+impl Oracle {
+    fn available(&self, client: ClientId) -> i64 {
+        self.accounts.get(&client).map_or(0, |a| a.available)
+    }
+
+    fn client_of(&self, tx: TxId) -> ClientId {
+        self.transactions[&tx].client
+    }
+
+    fn is_stored(&self, tx: TxId) -> bool {
+        self.transactions.contains_key(&tx)
+    }
+
+    fn apply(&mut self, record: &CSVRecord) {
+        let amount = record.amount.as_ref().and_then(to_units);
+
+        match record.r#type {
+            TxType::Deposit | TxType::Withdrawal => {
+                // Strict validation drops a missing, zero or negative amount before the engine
+                // sees it, so no account is opened for it
+                let Some(amount) = amount.filter(|amount| *amount > 0) else {
+                    return;
+                };
+                if self.transactions.contains_key(&record.tx) {
+                    return;
+                }
+                let account = self.accounts.entry(record.client).or_default();
+                if account.locked {
+                    return;
+                }
+                let is_withdrawal = record.r#type == TxType::Withdrawal;
+                if is_withdrawal && account.available < amount {
+                    return;
+                }
+
+                if is_withdrawal {
+                    account.available -= amount;
+                } else {
+                    account.available += amount;
+                }
+                self.transactions.insert(
+                    record.tx,
+                    OracleTransaction {
+                        client: record.client,
+                        r#type: record.r#type.clone(),
+                        amount,
+                        status: TransactionStatus::Processed,
+                    },
+                );
+            }
+            TxType::Dispute | TxType::Resolve | TxType::Chargeback => {
+                let Some(tx) = self.transactions.get_mut(&record.tx) else {
+                    return;
+                };
+                if tx.client != record.client || tx.r#type != TxType::Deposit {
+                    return;
+                }
+                let account = self.accounts.entry(record.client).or_default();
+
+                match (&record.r#type, &tx.status) {
+                    (
+                        TxType::Dispute,
+                        TransactionStatus::Processed | TransactionStatus::Resolved,
+                    ) => {
+                        account.available -= tx.amount;
+                        account.held += tx.amount;
+                        tx.status = TransactionStatus::Disputed;
+                    }
+                    (TxType::Resolve, TransactionStatus::Disputed) => {
+                        account.available += tx.amount;
+                        account.held -= tx.amount;
+                        tx.status = TransactionStatus::Resolved;
+                    }
+                    (TxType::Chargeback, TransactionStatus::Disputed) => {
+                        account.held -= tx.amount;
+                        account.locked = true;
+                        tx.status = TransactionStatus::Chargedback;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn output_records(&self) -> Vec<OutputRecord> {
+        self.accounts
+            .iter()
+            .map(|(client, account)| OutputRecord {
+                client: *client,
+                available: to_decimal(account.available),
+                held: to_decimal(account.held),
+                total: to_decimal(account.available + account.held),
+                locked: account.locked,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        file_reader::raw_csv_stream,
+        ledger::engine::PaymentsEngine,
+        output::output_records,
+        precision::PrecisionPolicy,
+        validation::check_strict,
+        workload::{Workload, WorkloadConfig, WorkloadError},
+    };
+
+    #[test]
+    fn test_validate_rejects_ratios_that_are_not_probabilities() {
+        assert_eq!(WorkloadConfig::default().validate(), Ok(()));
+
+        let config = WorkloadConfig {
+            error_rate: 1.5,
+            ..WorkloadConfig::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(WorkloadError::RatioOutOfRange {
+                name: "error_rate",
+                value: 1.5
+            })
+        );
+
+        let config = WorkloadConfig {
+            withdrawal_ratio: f64::NAN,
+            ..WorkloadConfig::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = WorkloadConfig {
+            dispute_ratio: 0.5,
+            resolve_ratio: 0.4,
+            chargeback_ratio: 0.2,
+            ..WorkloadConfig::default()
+        };
+        assert!(matches!(
+            config.validate(),
+            Err(WorkloadError::DisputeRatiosExceedOne(_))
+        ));
+    }
+
+    #[test]
+    fn test_expected_output_matches_engine() {
+        // Enough clients that some are first seen in a rejected record
+        let config = WorkloadConfig {
+            clients: 1_000,
+            transactions: 5_000,
+            dispute_ratio: 0.1,
+            resolve_ratio: 0.05,
+            chargeback_ratio: 0.02,
+            error_rate: 0.05,
+            ..WorkloadConfig::default()
+        };
+        let workload = Workload::generate(&config);
+        assert_eq!(workload.records.len(), 5_000);
+
+        // Go through the written CSV and strict validation, as `cli` does
+        let mut input = Vec::new();
+        workload.write_csv(&mut input).unwrap();
+        let mut payment_engine = PaymentsEngine::default();
+        let mut rejected = 0;
+        for record in raw_csv_stream(input.as_slice()) {
+            let result = check_strict(record.unwrap())
+                .and_then(|record| payment_engine.process_csv_record(record));
+            rejected += usize::from(result.is_err());
+        }
+        assert!(rejected > 0);
+
+        let mut actual = Vec::new();
+        let mut csv_wtr = csv::Writer::from_writer(&mut actual);
+        for account in output_records(&payment_engine.client_manager, &PrecisionPolicy::default()) {
+            csv_wtr.serialize(account).unwrap();
+        }
+        drop(csv_wtr);
+
+        let mut expected = Vec::new();
+        Workload::generate(&config)
+            .write_expected(&mut expected)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(actual).unwrap(),
+            String::from_utf8(expected).unwrap()
+        );
+    }
+}
@@ -0,0 +1,644 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+fn cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cli"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .expect("cli should run")
+}
+
+/// The latest state saved at `path` as a base plus a log of changes beside it, as far as the
+/// fields read here go: each change replaces the fields it has, except `manifest`, which it
+/// extends.
+fn saved_state(path: &Path) -> Option<serde_json::Value> {
+    let base = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(path).ok()?);
+    let base = base.ok()?;
+    let mut state = base["state"].clone();
+    let log = std::fs::read_to_string(format!("{}.log", path.display())).unwrap_or_default();
+    for line in log.lines() {
+        let Ok(logged) = serde_json::from_str::<serde_json::Value>(line) else {
+            break;
+        };
+        if logged["log"] != base["log"] {
+            continue;
+        }
+        for (field, value) in logged["changes"].as_object()? {
+            match (field.as_str(), state[field].as_array_mut()) {
+                ("manifest", Some(manifest)) => manifest.extend(value.as_array()?.iter().cloned()),
+                _ => state[field] = value.clone(),
+            }
+        }
+    }
+    Some(state)
+}
+
+const INPUT: &str = "tests/scenarios/multiple_disputes_then_chargeback/input.csv";
+const EXPECTED: &str = "tests/scenarios/multiple_disputes_then_chargeback/expected_output.csv";
+
+#[test]
+fn missing_input_exits_with_open_error() {
+    let output = cli(&["process", "does/not/exist.csv"]);
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn validate_reports_findings() {
+    let output = cli(&[
+        "validate",
+        "tests/scenarios/duplicate_and_unknown_transactions/input.csv",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = cli(&["validate", INPUT]);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn validate_reports_every_malformed_row() {
+    let run = |args: &[&str]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_cli"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"type,client,tx,amount\nbogus,1,1,1.0\ndeposit,x,2,1.0\ndeposit,1,3,-1\n")
+            .unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = run(&["validate", "--error-format", "json"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let codes = stderr
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .map(|rejection| {
+            (
+                rejection["record"].clone(),
+                rejection["error"]["code"].clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        codes,
+        [
+            (1.into(), "malformed".into()),
+            (2.into(), "malformed".into()),
+            (3.into(), "negative_amount".into()),
+        ]
+    );
+    assert!(stderr.ends_with("3 invalid record(s)\n"));
+
+    // `process` still stops at the first malformed row
+    assert_eq!(run(&["process"]).status.code(), Some(4));
+}
+
+#[test]
+fn scales_out_of_range_are_usage_errors() {
+    for scale in ["--scale=-3", "--scale=29", "--display-scale=4000000000"] {
+        let output = cli(&["process", INPUT, scale]);
+        assert_eq!(output.status.code(), Some(2), "{scale}");
+    }
+    assert!(cli(&["process", INPUT, "--scale=28"]).status.success());
+}
+
+#[test]
+fn inspect_client_and_tx() {
+    let output = cli(&["inspect", INPUT, "--client", "1"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,20.0000,0,20.0000,true\n"
+    );
+
+    let output = cli(&["inspect", INPUT, "--tx", "1"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "tx,client,type,amount,status\n1,1,deposit,10.0000,chargedback\n"
+    );
+
+    let output = cli(&["inspect", INPUT, "--tx", "42"]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn diff_compares_numerically() {
+    let output = cli(&["diff", EXPECTED, EXPECTED]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = cli(&[
+        "diff",
+        EXPECTED,
+        "tests/scenarios/deposits_and_withdrawals/expected_output.csv",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn batch_policy_skip_applies_the_rest_of_a_batch() {
+    let input = "tests/scenarios/atomic_batches/input.csv";
+    let output = cli(&["process", input, "--batch-policy", "skip"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,70.0000,0,70.0000,false\n2,25.0000,0,25.0000,false\n3,1.0000,0,1.0000,false\n"
+    );
+    // Nothing is rolled back, so the rejections do not name a batch
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 3);
+    assert!(!stderr.contains("rolled back"));
+}
+
+#[test]
+fn multiple_inputs_share_one_engine() {
+    let input = "tests/scenarios/deposits_and_withdrawals/input.csv";
+    let output = cli(&["process", input, input, "--error-format", "json"]);
+    assert_eq!(output.status.code(), Some(0));
+
+    // Every stored TxId from the first copy is rejected in the second; TxId 5 was never stored
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let duplicates = stderr
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .filter(|rejection| {
+            rejection["input"] == input && rejection["error"]["code"] == "duplicate_transaction_id"
+        })
+        .count();
+    assert_eq!(duplicates, 4);
+}
+
+#[test]
+fn stdin_and_globs() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["process", "-", "tests/scenarios/rounding_*/input.csv"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"type,client,tx,amount\ndeposit,3,100,1.5\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,2.0002,0,2.0002,false\n2,0.1233,0,0.1233,false\n3,1.5000,0,1.5000,false\n"
+    );
+
+    let output = cli(&["process", "tests/scenarios/*/missing.csv"]);
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_resumes_from_checkpoint_and_dumps_on_signal() {
+    use std::{
+        fs::{self, OpenOptions},
+        io::{BufRead, BufReader},
+        path::Path,
+        process::Child,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    let dir = std::env::temp_dir().join(format!("silhouette-follow-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("input.csv");
+    let checkpoint = dir.join("checkpoint.json");
+
+    let append = |text: &str| {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&input)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    };
+    let spawn_with = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_cli"))
+            .args([
+                "follow",
+                "--poll-interval",
+                "10",
+                "--checkpoint-interval",
+                "0",
+            ])
+            .args(args)
+            .arg("--checkpoint")
+            .args([&checkpoint, &input])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap()
+    };
+    let spawn = || spawn_with(&[]);
+    let wait_for_records = |records: u64| {
+        let started = Instant::now();
+        loop {
+            let applied =
+                saved_state(&checkpoint).and_then(|checkpoint| checkpoint["records"].as_u64());
+            if applied == Some(records) {
+                return;
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "timed out at {applied:?}"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    };
+    let signal = |child: &Child, name: &str| {
+        let status = Command::new("kill")
+            .args([format!("-{name}"), child.id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+
+    // A file without its header yet can still be stopped
+    append("");
+    let child = spawn();
+    thread::sleep(Duration::from_millis(250));
+    signal(&child, "TERM");
+    assert!(child.wait_with_output().unwrap().status.success());
+    assert!(!Path::new(&checkpoint).exists());
+
+    // The second record is only half written, so it must wait for its newline
+    append("type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,1,2,5");
+    let mut child = spawn();
+    wait_for_records(1);
+    append(".0\nwithdrawal,1,3,2\n");
+    wait_for_records(3);
+
+    signal(&child, "USR1");
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut dump = String::new();
+    while !dump.starts_with("1,") {
+        dump.clear();
+        stdout.read_line(&mut dump).unwrap();
+    }
+    assert_eq!(dump, "1,13.0000,0,13.0000,false\n");
+
+    signal(&child, "TERM");
+    assert!(child.wait().unwrap().success());
+
+    // Malformed rows are reported in the requested error format
+    append("deposit,2,4,1\nbogus,2,5,1\n");
+    let child = spawn_with(&["--error-format", "json"]);
+    wait_for_records(5);
+    signal(&child, "TERM");
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,13.0000,0,13.0000,false\n2,1.0000,0,1.0000,false\n"
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let rejection = serde_json::from_str::<serde_json::Value>(stderr.trim()).unwrap();
+    assert_eq!(rejection["record"], 5);
+    assert_eq!(rejection["error"]["code"], "malformed");
+
+    // A replaced file is refused rather than resumed at a stale offset
+    let rotated = dir.join("rotated.csv");
+    fs::write(&rotated, "type,client,tx,amount\ndeposit,3,9,1\n").unwrap();
+    fs::rename(&rotated, &input).unwrap();
+    let output = spawn().wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+
+    assert!(Path::new(&checkpoint).exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn watch_applies_each_inbox_file_once() {
+    use std::fs;
+
+    let dir = std::env::temp_dir().join(format!("silhouette-watch-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let (inbox, outbox) = (dir.join("inbox"), dir.join("outbox"));
+    fs::create_dir_all(&inbox).unwrap();
+    let watch = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+            .args(["watch", "--once", "--outbox"])
+            .args([&outbox, &inbox])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    let first = "type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,50\n";
+    fs::write(inbox.join("a.csv"), first).unwrap();
+    fs::write(
+        inbox.join("b.csv"),
+        "type,client,tx,amount\ndeposit,1,3,1\n",
+    )
+    .unwrap();
+    fs::write(
+        inbox.join("c.csv"),
+        "type,client,tx,amount\ndeposit,1,4,2\nbad\n",
+    )
+    .unwrap();
+    fs::write(inbox.join(".partial.csv"), "type,client").unwrap();
+    let stderr = watch();
+    let lines = stderr.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines[..2],
+        [
+            "a.csv: done, 2 record(s), 1 rejected",
+            "b.csv: done, 1 record(s), 0 rejected"
+        ]
+    );
+    assert!(lines[2].starts_with("c.csv: failed, ") && lines[2].contains("malformed input"));
+    assert_eq!(
+        fs::read_to_string(outbox.join("b.csv.accounts.csv")).unwrap(),
+        "client,available,held,total,locked\n1,11.0000,0,11.0000,false\n"
+    );
+    let rejections = fs::read_to_string(outbox.join("a.csv.rejections.jsonl")).unwrap();
+    assert!(rejections.contains(r#""record":2,"error":{"code":"insufficient_funds""#));
+    assert!(inbox.join("done/a.csv").exists() && inbox.join("failed/c.csv").exists());
+    assert!(inbox.join(".partial.csv").exists());
+
+    // A renamed copy of an applied file is not applied again; c.csv's deposit was rolled back
+    fs::write(inbox.join("a-again.csv"), first).unwrap();
+    fs::write(
+        inbox.join("d.csv"),
+        "type,client,tx,amount\ndeposit,1,5,4\n",
+    )
+    .unwrap();
+    assert_eq!(
+        watch(),
+        "a-again.csv: skipped, already processed as a.csv\nd.csv: done, 1 record(s), 0 rejected\n"
+    );
+    assert_eq!(
+        fs::read_to_string(outbox.join("d.csv.accounts.csv")).unwrap(),
+        "client,available,held,total,locked\n1,15.0000,0,15.0000,false\n"
+    );
+    assert!(inbox.join("failed/a-again.csv").exists());
+
+    // A new file reusing an earlier name is stored under a prefixed name rather than overwriting
+    fs::write(
+        inbox.join("b.csv"),
+        "type,client,tx,amount\ndeposit,1,6,5\n",
+    )
+    .unwrap();
+    let stderr = watch();
+    let stored = stderr
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("b.csv: name already used, stored as "))
+        .unwrap()
+        .to_owned();
+    assert!(stored.ends_with("-b.csv"));
+    assert!(stderr.ends_with("b.csv: done, 1 record(s), 0 rejected\n"));
+    assert_eq!(
+        fs::read_to_string(outbox.join("b.csv.accounts.csv")).unwrap(),
+        "client,available,held,total,locked\n1,11.0000,0,11.0000,false\n"
+    );
+    assert_eq!(
+        fs::read_to_string(outbox.join(format!("{stored}.accounts.csv"))).unwrap(),
+        "client,available,held,total,locked\n1,20.0000,0,20.0000,false\n"
+    );
+    assert_eq!(
+        fs::read_to_string(inbox.join("done/b.csv")).unwrap(),
+        "type,client,tx,amount\ndeposit,1,3,1\n"
+    );
+    assert!(inbox.join("done").join(&stored).exists());
+
+    // A run that stopped after saving but before moving a file finishes the move on restart
+    let state_path = outbox.join("state.json");
+    let state = saved_state(&state_path).unwrap();
+    let sha256 = state["manifest"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["file"] == "d.csv")
+        .unwrap()["sha256"]
+        .clone();
+    let log_path = outbox.join("state.json.log");
+    let log = fs::read_to_string(&log_path).unwrap();
+    let mut logged =
+        serde_json::from_str::<serde_json::Value>(log.lines().last().unwrap()).unwrap();
+    let changes = &mut logged["changes"];
+    changes["manifest"] = serde_json::json!([]);
+    changes["in_progress"] = serde_json::json!({ "file": "d.csv", "sha256": sha256 });
+    for (field, empty) in [
+        ("accounts", "{}"),
+        ("transactions", "{}"),
+        ("journal", "[]"),
+    ] {
+        changes["engine"][field] = serde_json::from_str(empty).unwrap();
+    }
+    fs::write(&log_path, format!("{log}{logged}\n")).unwrap();
+    fs::rename(inbox.join("done/d.csv"), inbox.join("d.csv")).unwrap();
+    assert_eq!(watch(), "d.csv: recovered, moved to done/\n");
+    assert!(inbox.join("done/d.csv").exists());
+
+    // Without the marker, the same file dropped again is a duplicate, not a recovery
+    fs::copy(inbox.join("done/d.csv"), inbox.join("d.csv")).unwrap();
+    assert_eq!(watch(), "d.csv: skipped, already processed as d.csv\n");
+    assert!(fs::read_dir(inbox.join("failed")).unwrap().any(|entry| {
+        entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .ends_with("-d.csv")
+    }));
+    assert_eq!(
+        fs::read_to_string(outbox.join("d.csv.accounts.csv")).unwrap(),
+        "client,available,held,total,locked\n1,15.0000,0,15.0000,false\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn resume_from_checkpoint_matches_uninterrupted_run() {
+    let input = "tests/scenarios/duplicate_and_unknown_transactions/input.csv";
+    let checkpoint = std::env::temp_dir()
+        .join(format!("silhouette-resume-{}.json", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    let checkpoint_args = ["--checkpoint", &checkpoint, "--checkpoint-every", "4"];
+
+    // Six records leave the last checkpoint after the fourth, as if the run had died there
+    let full = cli(&[&["process", input][..], &checkpoint_args].concat());
+    assert!(full.status.success());
+    assert_eq!(String::from_utf8_lossy(&full.stderr).lines().count(), 5);
+
+    let resumed = cli(&[&["process", input, "--resume"][..], &checkpoint_args].concat());
+    assert!(resumed.status.success());
+    assert_eq!(resumed.stdout, full.stdout);
+    let stderr = String::from_utf8(resumed.stderr).unwrap();
+    let reported = stderr
+        .lines()
+        .map(|line| line.split(": ").nth(1).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(reported, ["record 5", "record 6"]);
+
+    // The uncompressed input is picked up at its byte offset rather than read again
+    let saved = saved_state(Path::new(&checkpoint)).unwrap();
+    assert_eq!(saved["input_position"]["records"], 4);
+    assert!(saved["input_position"]["offset"].as_u64().unwrap() > 0);
+
+    // Inputs other than those the checkpoint was taken over are refused
+    let mismatched = cli(&[&["process", input, input, "--resume"][..], &checkpoint_args].concat());
+    assert_eq!(mismatched.status.code(), Some(2));
+    std::fs::remove_file(&checkpoint).unwrap();
+
+    // A compressed input cannot be seeked, so it is read again up to the checkpoint
+    let gz = format!("{checkpoint}.csv.gz");
+    let mut encoder = flate2::write::GzEncoder::new(
+        std::fs::File::create(&gz).unwrap(),
+        flate2::Compression::fast(),
+    );
+    encoder.write_all(&std::fs::read(input).unwrap()).unwrap();
+    encoder.finish().unwrap();
+    cli(&[&["process", &gz][..], &checkpoint_args].concat());
+    let resumed = cli(&[&["process", &gz, "--resume"][..], &checkpoint_args].concat());
+    assert_eq!(resumed.stdout, full.stdout);
+    assert_eq!(String::from_utf8_lossy(&resumed.stderr).lines().count(), 2);
+
+    std::fs::remove_file(&checkpoint).unwrap();
+    std::fs::remove_file(format!("{checkpoint}.log")).unwrap();
+    std::fs::remove_file(&gz).unwrap();
+}
+
+#[test]
+fn changes_file_has_a_row_per_applied_record() {
+    let changes = std::env::temp_dir()
+        .join(format!("silhouette-changes-{}.csv", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    let output = cli(&["process", INPUT, "--changes", &changes]);
+    assert!(output.status.success());
+
+    let rows = std::fs::read_to_string(&changes).unwrap();
+    let rows = rows.lines().collect::<Vec<_>>();
+    assert_eq!(
+        rows[0],
+        "seq,client,tx,operation,available_change,held_change,available,held,total,locked"
+    );
+    // The rejected deposit to the locked account has no row, and `seq` skips its record number
+    assert_eq!(rows.len(), 10);
+    assert_eq!(
+        rows[7],
+        "7,1,1,chargeback,0,-10.0000,0,20.0000,20.0000,true"
+    );
+    assert_eq!(
+        rows[9],
+        "10,2,3,resolve,5.0000,-5.0000,5.0000,0,5.0000,false"
+    );
+
+    // An accepted record that changes nothing still has a row
+    let input =
+        std::env::temp_dir().join(format!("silhouette-changes-{}.in.csv", std::process::id()));
+    std::fs::write(
+        &input,
+        "type,client,tx,amount\ndeposit,1,1,5.0\nwithdrawal,1,2,2.0\ndispute,1,2,\n",
+    )
+    .unwrap();
+    let output = cli(&["process", input.to_str().unwrap(), "--changes", &changes]);
+    assert!(output.status.success());
+    let rows = std::fs::read_to_string(&changes).unwrap();
+    assert_eq!(
+        rows.lines().last().unwrap(),
+        "3,1,2,dispute,0,0,3.0000,0,3.0000,false"
+    );
+    std::fs::remove_file(&input).unwrap();
+    std::fs::remove_file(&changes).unwrap();
+
+    let output = cli(&["process", INPUT, "--changes", &changes, "--resume"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn as_of_answers_from_the_balance_history() {
+    let output = cli(&["process", INPUT, "--as-of", "6"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,0,30.0000,30.0000,false\n2,0,5.0000,5.0000,false\n"
+    );
+
+    let output = cli(&["inspect", INPUT, "--client", "1", "--as-of", "7"]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,available,held,total,locked\n1,0,20.0000,20.0000,true\n"
+    );
+
+    // The history is saved with the checkpoint, so a resumed run can still look back
+    let checkpoint = std::env::temp_dir()
+        .join(format!("silhouette-as-of-{}.json", std::process::id()))
+        .to_string_lossy()
+        .into_owned();
+    let checkpoint_args = ["--checkpoint", &checkpoint, "--checkpoint-every", "8"];
+    cli(&[&["process", INPUT, "--as-of", "1"][..], &checkpoint_args].concat());
+    let resumed = cli(&[
+        &["process", INPUT, "--resume", "--as-of", "6"][..],
+        &checkpoint_args,
+    ]
+    .concat());
+    assert_eq!(
+        resumed.stdout,
+        cli(&["process", INPUT, "--as-of", "6"]).stdout
+    );
+
+    cli(&[&["process", INPUT][..], &checkpoint_args].concat());
+    let resumed = cli(&[
+        &["process", INPUT, "--resume", "--as-of", "6"][..],
+        &checkpoint_args,
+    ]
+    .concat());
+    assert_eq!(resumed.status.code(), Some(2));
+    std::fs::remove_file(&checkpoint).unwrap();
+}
+
+#[test]
+fn statement_covers_a_record_range() {
+    let output = cli(&[
+        "statement",
+        INPUT,
+        "--client",
+        "1",
+        "--from",
+        "4",
+        "--to",
+        "7",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "client,record,tx,operation,amount,available,held,total,locked\n\
+         1,,,opening,,30.0000,0,30.0000,false\n\
+         1,4,1,dispute,10.0000,20.0000,10.0000,30.0000,false\n\
+         1,5,2,dispute,20.0000,0,30.0000,30.0000,false\n\
+         1,7,1,chargeback,10.0000,0,20.0000,20.0000,true\n\
+         1,,,closing,,0,20.0000,20.0000,true\n"
+    );
+
+    let output = cli(&["statement", INPUT, "--client", "1", "--text"]);
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.starts_with("Statement for client 1, records 1 to 10\n"));
+    assert!(text.contains("resolve"));
+    assert!(text.ends_with("The account is locked.\n"));
+
+    let output = cli(&["statement", INPUT, "--client", "9"]);
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,107 @@
+//! Streams CSV lines into the socket ingester. Run with `--features server`.
+#![cfg(feature = "server")]
+
+use silhouette::{ingest, ledger::engine::PaymentsEngine, server::EngineHandle};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+/// Sends `lines` then half-closes, returning every reply line.
+async fn produce<S: AsyncRead + AsyncWrite>(stream: S, lines: &[&str]) -> Vec<String> {
+    let (reader, mut writer) = tokio::io::split(stream);
+    for line in lines {
+        writer
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .unwrap();
+    }
+    writer.shutdown().await.unwrap();
+
+    let mut replies = Vec::new();
+    let mut reader = BufReader::new(reader).lines();
+    while let Some(line) = reader.next_line().await.unwrap() {
+        replies.push(line);
+    }
+    replies
+}
+
+#[tokio::test]
+async fn tcp_producers_share_one_engine() {
+    let handle = EngineHandle::spawn(PaymentsEngine::default());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(ingest::serve_tcp(listener, handle.clone(), |err| {
+        panic!("{err}")
+    }));
+
+    let first = produce(
+        TcpStream::connect(addr).await.unwrap(),
+        &[
+            "deposit,1,1,10.0",
+            "",
+            "withdrawal, 1, 2, 25",
+            "dispute,1,1",
+        ],
+    );
+    let second = produce(
+        TcpStream::connect(addr).await.unwrap(),
+        &[
+            "deposit,2,3,5",
+            "deposit,2,3,5",
+            "deposit,two,4,1",
+            "deposit,2,5,1e5",
+        ],
+    );
+    let (first, second) = tokio::join!(first, second);
+
+    assert_eq!(first[0], "ok 1");
+    assert!(first[1].starts_with("error 2 insufficient_funds: "));
+    assert_eq!(first[2], "ok 1");
+    assert_eq!(first.len(), 3);
+
+    assert_eq!(second[0], "ok 3");
+    assert!(second[1].starts_with("error 3 duplicate_transaction_id: "));
+    assert!(second[2].starts_with("error malformed: "));
+    assert!(second[3].starts_with("error 5 exponent_notation: "));
+
+    let accounts = handle.accounts().await.unwrap();
+    assert_eq!(accounts.len(), 2);
+    assert_eq!(accounts[0].held, "10".parse().unwrap());
+    assert_eq!(accounts[1].available, "5".parse().unwrap());
+}
+
+#[tokio::test]
+async fn overlong_lines_are_malformed_and_skipped() {
+    let handle = EngineHandle::spawn(PaymentsEngine::default());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(ingest::serve_tcp(listener, handle, |err| panic!("{err}")));
+
+    let long = format!("deposit,1,1,{}", "1".repeat(ingest::MAX_LINE_LEN * 4));
+    let replies = produce(
+        TcpStream::connect(addr).await.unwrap(),
+        &[&long, "deposit,1,2,1.0"],
+    )
+    .await;
+    assert_eq!(replies.len(), 2);
+    assert!(replies[0].starts_with("error malformed: line longer than"));
+    assert_eq!(replies[1], "ok 2");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn unix_socket_producer() {
+    let path = std::env::temp_dir().join(format!("silhouette-ingest-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let handle = EngineHandle::spawn(PaymentsEngine::default());
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+    tokio::spawn(ingest::serve_unix(listener, handle, |err| panic!("{err}")));
+
+    let stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    let replies = produce(stream, &["deposit,1,1,1.5", "chargeback,1,1,"]).await;
+    assert_eq!(replies, ["ok 1", "ok 1"]);
+
+    std::fs::remove_file(&path).unwrap();
+}
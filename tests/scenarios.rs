@@ -0,0 +1,164 @@
+//! Golden-file scenarios: every folder under `tests/scenarios/` holds an `input.csv` which is run
+//! through the `cli` binary, with stdout compared against `expected_output.csv` and, when present,
+//! the JSON rejections on stderr compared against the ones in `expected_errors.jsonl`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The fields of a `--error-format json` rejection a scenario pins down. Messages are left out
+/// so rewording one does not break every scenario.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExpectedError {
+    record: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    batch: Option<String>,
+    code: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    client: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tx: Option<u64>,
+}
+
+impl ExpectedError {
+    fn from_rejection(line: &str) -> Self {
+        let rejection = serde_json::from_str::<Value>(line)
+            .unwrap_or_else(|err| panic!("stderr line `{line}` is not a JSON rejection: {err}"));
+        let error = &rejection["error"];
+        Self {
+            record: rejection["record"].as_u64().unwrap(),
+            batch: rejection["batch"].as_str().map(str::to_owned),
+            code: error["code"].as_str().unwrap().to_owned(),
+            client: error["client"].as_u64(),
+            tx: error["tx"].as_u64(),
+        }
+    }
+
+    fn render(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+}
+
+fn scenario_dirs() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios");
+    let mut dirs = fs::read_dir(root)
+        .expect("tests/scenarios should exist")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs
+}
+
+/// Line diff based on the longest common subsequence, rendered like `diff -u` without hunks.
+fn diff(expected: &[&str], actual: &[&str]) -> String {
+    let (n, m) = (expected.len(), actual.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            out += &format!("  {}\n", expected[i]);
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lcs[i][j + 1] > lcs[i + 1][j]) {
+            out += &format!("+ {}\n", actual[j]);
+            j += 1;
+        } else {
+            out += &format!("- {}\n", expected[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+fn check(name: &str, kind: &str, expected: &[&str], actual: &[&str], failures: &mut Vec<String>) {
+    if expected != actual {
+        failures.push(format!(
+            "scenario `{name}`: {kind} mismatch (- expected, + actual)\n{}",
+            diff(expected, actual)
+        ));
+    }
+}
+
+fn run_scenario(dir: &Path, failures: &mut Vec<String>) {
+    let name = dir.file_name().unwrap().to_string_lossy();
+    let output = Command::new(env!("CARGO_BIN_EXE_cli"))
+        .current_dir(dir)
+        .args(["input.csv", "--error-format", "json"])
+        .output()
+        .expect("cli should run");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let expected_output = fs::read_to_string(dir.join("expected_output.csv"))
+        .unwrap_or_else(|_| panic!("scenario `{name}` is missing expected_output.csv"));
+    check(
+        &name,
+        "output",
+        &expected_output.lines().collect::<Vec<_>>(),
+        &stdout.lines().collect::<Vec<_>>(),
+        failures,
+    );
+
+    if let Ok(expected_errors) = fs::read_to_string(dir.join("expected_errors.jsonl")) {
+        let expected_errors = expected_errors
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str::<ExpectedError>(line)
+                    .unwrap()
+                    .render()
+            })
+            .collect::<Vec<_>>();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        let actual_errors = stderr
+            .lines()
+            .map(|line| ExpectedError::from_rejection(line).render())
+            .collect::<Vec<_>>();
+        check(
+            &name,
+            "errors",
+            &expected_errors
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>(),
+            &actual_errors.iter().map(String::as_str).collect::<Vec<_>>(),
+            failures,
+        );
+    }
+}
+
+#[test]
+fn scenarios() {
+    let mut failures = Vec::new();
+    let dirs = scenario_dirs();
+    assert!(!dirs.is_empty());
+
+    for dir in &dirs {
+        run_scenario(dir, &mut failures);
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} scenarios failed\n\n{}",
+        failures.len(),
+        dirs.len(),
+        failures.join("\n")
+    );
+}
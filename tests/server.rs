@@ -0,0 +1,113 @@
+//! Exercises the HTTP API on a real localhost socket. Run with `--features server`.
+#![cfg(feature = "server")]
+
+use std::net::SocketAddr;
+
+use serde_json::{Value, json};
+use silhouette::{
+    ledger::engine::PaymentsEngine,
+    server::{EngineHandle, serve},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+async fn start() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(serve(
+        listener,
+        EngineHandle::spawn(PaymentsEngine::default()),
+    ));
+    addr
+}
+
+/// Minimal HTTP/1.1 client: one request per connection, JSON in and out.
+async fn request(addr: SocketAddr, method: &str, path: &str, body: Option<Value>) -> (u16, Value) {
+    let body = body.map(|body| body.to_string()).unwrap_or_default();
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\
+         Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn transactions_and_accounts_round_trip() {
+    let addr = start().await;
+
+    let (status, outcomes) = request(
+        addr,
+        "POST",
+        "/transactions",
+        Some(json!([
+            {"type": "deposit", "client": 1, "tx": 1, "amount": "10.5"},
+            {"type": "deposit", "client": 2, "tx": 2, "amount": 3},
+            {"type": "withdrawal", "client": 1, "tx": 3, "amount": "20"},
+            {"type": "dispute", "client": 2, "tx": 2},
+        ])),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(outcomes[0], json!({"status": "applied", "tx": 1}));
+    assert_eq!(outcomes[2]["status"], "rejected");
+    assert_eq!(outcomes[2]["error"]["code"], "insufficient_funds");
+    assert_eq!(outcomes[3]["status"], "applied");
+
+    let (status, outcome) = request(
+        addr,
+        "POST",
+        "/transactions",
+        Some(json!({"type": "withdrawal", "client": 1, "tx": 4, "amount": "0.5"})),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert_eq!(outcome, json!({"status": "applied", "tx": 4}));
+
+    let (status, outcome) = request(
+        addr,
+        "POST",
+        "/transactions",
+        Some(json!({"type": "deposit", "client": 1, "tx": 5, "amount": "1e5"})),
+    )
+    .await;
+    assert_eq!(status, 422);
+    assert_eq!(outcome["error"]["code"], "exponent_notation");
+
+    let (status, account) = request(addr, "GET", "/accounts/1", None).await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        account,
+        json!({"client": 1, "available": "10.0000", "held": "0", "total": "10.0000", "locked": false})
+    );
+
+    let (status, accounts) = request(addr, "GET", "/accounts", None).await;
+    assert_eq!(status, 200);
+    assert_eq!(accounts.as_array().unwrap().len(), 2);
+    assert_eq!(accounts[1]["held"], "3.0000");
+
+    let (status, transaction) = request(addr, "GET", "/transactions/2", None).await;
+    assert_eq!(status, 200);
+    assert_eq!(transaction["status"], "disputed");
+
+    let (status, _) = request(
+        addr,
+        "POST",
+        "/transactions",
+        Some(json!({"type": "deposit"})),
+    )
+    .await;
+    assert_eq!(status, 400);
+
+    assert_eq!(request(addr, "GET", "/accounts/9", None).await.0, 404);
+    assert_eq!(request(addr, "GET", "/transactions/9", None).await.0, 404);
+}